@@ -0,0 +1,449 @@
+//! # AES implemented by hand
+//! `rust-crypto` only gives us a black box, but for cryptanalysis we need to look inside:
+//! the number of rounds is configurable and every intermediate state can be observed.
+//!
+//! The state is kept in the FIPS-197 byte order, so `state[r + 4 * c]` is row `r`, column `c`.
+//! A reduced round variant still omits `MixColumns` in its last round, just like the full cipher.
+//! learned: the key schedule does not care how many rounds there are, it just keeps going
+
+use crate::blockcipher::BlockCipher;
+
+pub type State = [u8; 16];
+
+/// The individual transformations of a round
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Step {
+    SubBytes,
+    ShiftRows,
+    MixColumns,
+    AddRoundKey,
+}
+
+pub struct MyAes {
+    round_keys: Vec<State>,
+}
+
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+const INV_SBOX: [u8; 256] = [
+    0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
+    0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
+    0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
+    0x08, 0x2e, 0xa1, 0x66, 0x28, 0xd9, 0x24, 0xb2, 0x76, 0x5b, 0xa2, 0x49, 0x6d, 0x8b, 0xd1, 0x25,
+    0x72, 0xf8, 0xf6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xd4, 0xa4, 0x5c, 0xcc, 0x5d, 0x65, 0xb6, 0x92,
+    0x6c, 0x70, 0x48, 0x50, 0xfd, 0xed, 0xb9, 0xda, 0x5e, 0x15, 0x46, 0x57, 0xa7, 0x8d, 0x9d, 0x84,
+    0x90, 0xd8, 0xab, 0x00, 0x8c, 0xbc, 0xd3, 0x0a, 0xf7, 0xe4, 0x58, 0x05, 0xb8, 0xb3, 0x45, 0x06,
+    0xd0, 0x2c, 0x1e, 0x8f, 0xca, 0x3f, 0x0f, 0x02, 0xc1, 0xaf, 0xbd, 0x03, 0x01, 0x13, 0x8a, 0x6b,
+    0x3a, 0x91, 0x11, 0x41, 0x4f, 0x67, 0xdc, 0xea, 0x97, 0xf2, 0xcf, 0xce, 0xf0, 0xb4, 0xe6, 0x73,
+    0x96, 0xac, 0x74, 0x22, 0xe7, 0xad, 0x35, 0x85, 0xe2, 0xf9, 0x37, 0xe8, 0x1c, 0x75, 0xdf, 0x6e,
+    0x47, 0xf1, 0x1a, 0x71, 0x1d, 0x29, 0xc5, 0x89, 0x6f, 0xb7, 0x62, 0x0e, 0xaa, 0x18, 0xbe, 0x1b,
+    0xfc, 0x56, 0x3e, 0x4b, 0xc6, 0xd2, 0x79, 0x20, 0x9a, 0xdb, 0xc0, 0xfe, 0x78, 0xcd, 0x5a, 0xf4,
+    0x1f, 0xdd, 0xa8, 0x33, 0x88, 0x07, 0xc7, 0x31, 0xb1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xec, 0x5f,
+    0x60, 0x51, 0x7f, 0xa9, 0x19, 0xb5, 0x4a, 0x0d, 0x2d, 0xe5, 0x7a, 0x9f, 0x93, 0xc9, 0x9c, 0xef,
+    0xa0, 0xe0, 0x3b, 0x4d, 0xae, 0x2a, 0xf5, 0xb0, 0xc8, 0xeb, 0xbb, 0x3c, 0x83, 0x53, 0x99, 0x61,
+    0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d,
+];
+
+impl MyAes {
+    /// AES with the standard number of rounds for the key length (16, 24 or 32 bytes)
+    pub fn new(key: &[u8]) -> Self {
+        let rounds = match key.len() {
+            16 => 10,
+            24 => 12,
+            32 => 14,
+            len => panic!("no AES variant for a {} byte key", len),
+        };
+        MyAes::with_rounds(key, rounds)
+    }
+
+    /// AES with an arbitrary number of rounds, the last one is always without `MixColumns`
+    pub fn with_rounds(key: &[u8], rounds: usize) -> Self {
+        assert!(rounds >= 1, "need at least one round");
+        MyAes {
+            round_keys: expand_key(key, rounds),
+        }
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.round_keys.len() - 1
+    }
+
+    /// Round key `0` is the one added before the first round
+    pub fn round_key(&self, round: usize) -> State {
+        self.round_keys[round]
+    }
+
+    /// Encrypt a block, calling `observer` after every single step.
+    /// The observer receives the round (0 for the initial key addition) and may modify the state.
+    pub fn encrypt_block_observed(
+        &self,
+        block: &[u8],
+        observer: &mut dyn FnMut(usize, Step, &mut State),
+    ) -> State {
+        let mut state = to_state(block);
+        add_round_key(&mut state, &self.round_keys[0]);
+        observer(0, Step::AddRoundKey, &mut state);
+        let rounds = self.rounds();
+        for round in 1..=rounds {
+            sub_bytes(&mut state);
+            observer(round, Step::SubBytes, &mut state);
+            shift_rows(&mut state);
+            observer(round, Step::ShiftRows, &mut state);
+            if round != rounds {
+                mix_columns(&mut state);
+                observer(round, Step::MixColumns, &mut state);
+            }
+            add_round_key(&mut state, &self.round_keys[round]);
+            observer(round, Step::AddRoundKey, &mut state);
+        }
+        state
+    }
+
+    /// The state after each round, starting with the initial key addition.
+    /// The last entry is the ciphertext.
+    pub fn encrypt_states(&self, block: &[u8]) -> Vec<State> {
+        let mut states = Vec::new();
+        self.encrypt_block_observed(block, &mut |_, step, state| {
+            if step == Step::AddRoundKey {
+                states.push(*state);
+            }
+        });
+        states
+    }
+
+    pub fn decrypt_state(&self, block: &[u8]) -> State {
+        let mut state = to_state(block);
+        let rounds = self.rounds();
+        for round in (1..=rounds).rev() {
+            add_round_key(&mut state, &self.round_keys[round]);
+            if round != rounds {
+                inv_mix_columns(&mut state);
+            }
+            inv_shift_rows(&mut state);
+            inv_sub_bytes(&mut state);
+        }
+        add_round_key(&mut state, &self.round_keys[0]);
+        state
+    }
+}
+
+impl BlockCipher for MyAes {
+    fn block_size(&self) -> usize {
+        16
+    }
+
+    fn encrypt_block(&self, block: &[u8]) -> Vec<u8> {
+        self.encrypt_block_observed(block, &mut |_, _, _| {})
+            .to_vec()
+    }
+
+    fn decrypt_block(&self, block: &[u8]) -> Vec<u8> {
+        self.decrypt_state(block).to_vec()
+    }
+}
+
+fn to_state(block: &[u8]) -> State {
+    assert_eq!(block.len(), 16, "AES operates on 16 byte blocks");
+    let mut state = [0; 16];
+    state.copy_from_slice(block);
+    state
+}
+
+/// Expand a 16, 24 or 32 byte key into `rounds + 1` round keys
+pub fn expand_key(key: &[u8], rounds: usize) -> Vec<State> {
+    assert!(
+        key.len() == 16 || key.len() == 24 || key.len() == 32,
+        "invalid key length"
+    );
+    let nk = key.len() / 4;
+    let mut words: Vec<[u8; 4]> = key.chunks(4).map(|c| [c[0], c[1], c[2], c[3]]).collect();
+    let mut rcon = 1u8;
+    for i in nk..4 * (rounds + 1) {
+        let mut temp = words[i - 1];
+        if i % nk == 0 {
+            temp = [
+                SBOX[temp[1] as usize] ^ rcon,
+                SBOX[temp[2] as usize],
+                SBOX[temp[3] as usize],
+                SBOX[temp[0] as usize],
+            ];
+            rcon = xtime(rcon);
+        } else if nk > 6 && i % nk == 4 {
+            temp = [
+                SBOX[temp[0] as usize],
+                SBOX[temp[1] as usize],
+                SBOX[temp[2] as usize],
+                SBOX[temp[3] as usize],
+            ];
+        }
+        let prev = words[i - nk];
+        words.push([
+            prev[0] ^ temp[0],
+            prev[1] ^ temp[1],
+            prev[2] ^ temp[2],
+            prev[3] ^ temp[3],
+        ]);
+    }
+    words
+        .chunks(4)
+        .take(rounds + 1)
+        .map(|w| {
+            let mut round_key = [0; 16];
+            for (i, word) in w.iter().enumerate() {
+                round_key[4 * i..4 * i + 4].copy_from_slice(word);
+            }
+            round_key
+        })
+        .collect()
+}
+
+/// Walk the AES-128 key schedule backwards from the round key of `round` to the master key
+pub fn invert_key_schedule(round_key: &State, round: usize) -> Vec<u8> {
+    let mut words: Vec<[u8; 4]> = round_key
+        .chunks(4)
+        .map(|c| [c[0], c[1], c[2], c[3]])
+        .collect();
+    let mut rcons = vec![1u8];
+    for _ in 1..round {
+        let last = *rcons.last().unwrap();
+        rcons.push(xtime(last));
+    }
+    for r in (1..=round).rev() {
+        // w[i - 4] = w[i] ^ temp, where temp is derived from w[i - 1]
+        let mut previous = [[0u8; 4]; 4];
+        for i in (1..4).rev() {
+            for b in 0..4 {
+                previous[i][b] = words[i][b] ^ words[i - 1][b];
+            }
+        }
+        let temp = previous[3];
+        let temp = [
+            SBOX[temp[1] as usize] ^ rcons[r - 1],
+            SBOX[temp[2] as usize],
+            SBOX[temp[3] as usize],
+            SBOX[temp[0] as usize],
+        ];
+        for b in 0..4 {
+            previous[0][b] = words[0][b] ^ temp[b];
+        }
+        words = previous.to_vec();
+    }
+    words.concat()
+}
+
+/// Multiply by x in GF(2^8)
+pub fn xtime(b: u8) -> u8 {
+    if b & 0x80 != 0 {
+        (b << 1) ^ 0x1b
+    } else {
+        b << 1
+    }
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial
+pub fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            result ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    result
+}
+
+pub fn sbox(b: u8) -> u8 {
+    SBOX[b as usize]
+}
+
+pub fn inv_sbox(b: u8) -> u8 {
+    INV_SBOX[b as usize]
+}
+
+pub fn sub_bytes(state: &mut State) {
+    for b in state.iter_mut() {
+        *b = SBOX[*b as usize];
+    }
+}
+
+pub fn inv_sub_bytes(state: &mut State) {
+    for b in state.iter_mut() {
+        *b = INV_SBOX[*b as usize];
+    }
+}
+
+/// Row `r` is rotated left by `r` positions
+pub fn shift_rows(state: &mut State) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            state[r + 4 * c] = old[r + 4 * ((c + r) % 4)];
+        }
+    }
+}
+
+pub fn inv_shift_rows(state: &mut State) {
+    let old = *state;
+    for r in 1..4 {
+        for c in 0..4 {
+            state[r + 4 * ((c + r) % 4)] = old[r + 4 * c];
+        }
+    }
+}
+
+fn mix_column_with(state: &mut State, matrix: [u8; 4]) {
+    for c in 0..4 {
+        let col = [
+            state[4 * c],
+            state[4 * c + 1],
+            state[4 * c + 2],
+            state[4 * c + 3],
+        ];
+        for r in 0..4 {
+            state[4 * c + r] = (0..4)
+                .map(|i| gmul(matrix[(4 + i - r) % 4], col[i]))
+                .fold(0, |acc, x| acc ^ x);
+        }
+    }
+}
+
+pub fn mix_columns(state: &mut State) {
+    mix_column_with(state, [2, 3, 1, 1]);
+}
+
+pub fn inv_mix_columns(state: &mut State) {
+    mix_column_with(state, [14, 11, 13, 9]);
+}
+
+pub fn add_round_key(state: &mut State, round_key: &State) {
+    for (b, k) in state.iter_mut().zip(round_key.iter()) {
+        *b ^= k;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcipher::aes;
+    use crate::random_128_bit;
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    /// FIPS-197 Appendix C
+    #[test]
+    fn fips_197_vectors() {
+        let plain = h("00112233445566778899aabbccddeeff");
+        let cases = [
+            (
+                "000102030405060708090a0b0c0d0e0f",
+                "69c4e0d86a7b0430d8cdb78070b4c55a",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f1011121314151617",
+                "dda97ca4864cdfe06eaf70a0ec0d7191",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "8ea2b7ca516745bfeafc49904b496089",
+            ),
+        ];
+        for (key, cipher) in cases.iter() {
+            let aes = MyAes::new(&h(key));
+            assert_eq!(hex::encode(aes.encrypt_block(&plain)), *cipher, "{}", key);
+            assert_eq!(aes.decrypt_block(&h(cipher)), plain, "{}", key);
+        }
+    }
+
+    /// FIPS-197 Appendix A.1 and the round trace of Appendix C.1
+    #[test]
+    fn key_schedule_and_states() {
+        let aes = MyAes::new(&h("2b7e151628aed2a6abf7158809cf4f3c"));
+        assert_eq!(aes.rounds(), 10);
+        assert_eq!(
+            aes.round_key(1).to_vec(),
+            h("a0fafe1788542cb123a339392a6c7605")
+        );
+        assert_eq!(
+            aes.round_key(10).to_vec(),
+            h("d014f9a8c9ee2589e13f0cc8b6630ca6")
+        );
+
+        let aes = MyAes::new(&h("000102030405060708090a0b0c0d0e0f"));
+        let states = aes.encrypt_states(&h("00112233445566778899aabbccddeeff"));
+        assert_eq!(states.len(), 11);
+        assert_eq!(states[0].to_vec(), h("00102030405060708090a0b0c0d0e0f0"));
+        assert_eq!(states[1].to_vec(), h("89d810e8855ace682d1843d8cb128fe4"));
+        assert_eq!(states[10].to_vec(), h("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    }
+
+    #[test]
+    fn matches_rust_crypto() {
+        for keylen in [16, 24, 32].iter() {
+            let key: Vec<u8> = (0..*keylen).map(|_| rand::random()).collect();
+            let block = random_128_bit();
+            assert_eq!(
+                MyAes::new(&key).encrypt_block(&block),
+                aes(&key).encrypt_block(&block)
+            );
+        }
+    }
+
+    #[test]
+    fn reduced_rounds() {
+        let key = random_128_bit();
+        let block = random_128_bit();
+        for rounds in 1..=12 {
+            let aes = MyAes::with_rounds(&key, rounds);
+            assert_eq!(aes.rounds(), rounds);
+            let cipher = aes.encrypt_block(&block);
+            assert_eq!(aes.decrypt_block(&cipher), block, "{} rounds", rounds);
+        }
+        // more rounds just keep going with the same schedule
+        assert_eq!(
+            MyAes::with_rounds(&key, 4).round_key(4),
+            MyAes::new(&key).round_key(4)
+        );
+    }
+
+    #[test]
+    fn key_schedule_inversion() {
+        let key = random_128_bit();
+        let aes = MyAes::new(&key);
+        for round in 0..=10 {
+            assert_eq!(invert_key_schedule(&aes.round_key(round), round), key);
+        }
+    }
+
+    #[test]
+    fn inverse_steps() {
+        let mut state = to_state(&random_128_bit());
+        let original = state;
+        mix_columns(&mut state);
+        shift_rows(&mut state);
+        sub_bytes(&mut state);
+        inv_sub_bytes(&mut state);
+        inv_shift_rows(&mut state);
+        inv_mix_columns(&mut state);
+        assert_eq!(state, original);
+    }
+}
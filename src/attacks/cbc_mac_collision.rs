@@ -0,0 +1,74 @@
+//! # CBC-MAC as a hash function
+//!
+//! Requires:
+//! - a "hash" that is CBC-MAC with a zero iv and a key everybody knows (it ships with the code that checks it)
+//! - a format that tolerates a block of garbage, like a JavaScript comment
+//!
+//! Idea: with the key, CBC can be run backwards from any target hash.
+//! - the MAC of `pad(content) || x` is `E(E(mac(content) ^ x) ^ p)`, with `p` a full block of pkcs#7 padding
+//! - so `x = D(D(target) ^ p) ^ mac(content)` gives exactly the target hash
+//!
+//! `content` ends up padded with pkcs#7, so it ends in a comment that swallows the padding and `x`.
+//! If either contains a line break the comment ends early, add a space before the comment and try again.
+
+use crate::blockcipher::aes;
+use crate::cbc_mac::cbc_mac_fixed;
+use crate::{aes_ecb_decrypt, pad_pkcs7, xor};
+
+/// Append a comment, its padding and a block to `code` so that the CBC-MAC under `key` is `target`,
+/// `None` if no number of spaces before the comment keeps line breaks out of it
+pub fn forge_collision(key: &[u8], code: &[u8], target: &[u8]) -> Option<Vec<u8>> {
+    let padding_block = vec![16; 16];
+    let before_padding = xor(aes_ecb_decrypt(target, key), &padding_block);
+    let before_chaining = aes_ecb_decrypt(&before_padding, key);
+    (0..64).find_map(|spaces| {
+        let content = [code, &vec![b' '; spaces], b"//"].concat();
+        let chaining = cbc_mac_fixed(aes(key).as_ref(), &content);
+        let mut forged = pad_pkcs7(content, 16);
+        forged.extend(xor(before_chaining.clone(), &chaining));
+        if forged[code.len()..]
+            .iter()
+            .any(|b| *b == b'\n' || *b == b'\r')
+        {
+            None
+        } else {
+            Some(forged)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn javascript_snippet() {
+        let key = b"YELLOW SUBMARINE";
+        let hash = |snippet: &[u8]| cbc_mac_fixed(aes(key).as_ref(), snippet);
+        let original = b"alert('MZA who was that?');\n";
+        let target = hash(original);
+        assert_eq!(
+            target,
+            hex::decode("296b8d7cb78a243dda4d0a61d33bbdd1").unwrap()
+        );
+
+        let code = b"alert('Ayo, the Wu is back!');";
+        let forged = forge_collision(key, code, &target).expect("no collision found");
+        assert!(forged.starts_with(code));
+        assert_eq!(hash(&forged), target);
+        // everything after the code is one comment
+        let comment = &forged[code.len()..];
+        assert!(comment.trim_ascii_start().starts_with(b"//"));
+        assert!(!comment.iter().any(|b| *b == b'\n' || *b == b'\r'));
+    }
+
+    #[test]
+    fn any_target() {
+        let key = b"YELLOW SUBMARINE";
+        for _ in 0..100 {
+            let target = crate::random_128_bit();
+            let forged = forge_collision(key, b"alert(1);", &target).expect("no collision found");
+            assert_eq!(cbc_mac_fixed(aes(key).as_ref(), &forged), target);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! # CBC-MAC message forgery
+//!
+//! Requires:
+//! - a bank API that authenticates transfers with CBC-MAC under a key shared with its web client
+//! - an account at the bank, so the web client signs transfers from it for us
+//!
+//! ## Attacker controlled iv
+//! The first version sends `message || iv || mac` and the server recomputes the MAC with the sent iv.
+//! CBC only ever sees `iv ^ first_block`, so any change to the first block can be cancelled in the iv:
+//! - get a signed transfer `from=<us>&to=<us>&amount=1000000`
+//! - replace `from=<us>` with `from=<victim>` (same length) and xor the difference into the iv
+//!
+//! ## Length extension
+//! The second version fixes the iv to zero and sends `message || mac` with a list of transactions.
+//! The MAC of a captured message `m` is the chaining value after `pad(m)`, so for our own signed `m'`:
+//! `m || pad(m) || (m'_1 ^ mac) || m'_2.. ` has the same MAC as `m'`.
+//! The first block of `m'` turns into garbage, but everything after it is appended to the victim's transaction list.
+//! The victim's last transaction is glued to the garbage and gets lost, our own transactions behind it don't.
+
+use std::collections::HashMap;
+
+use crate::blockcipher::{aes, BlockCipher};
+use crate::cbc_mac::{cbc_mac, cbc_mac_fixed};
+use crate::{constant_time_eq, pad_pkcs7, random_128_bit};
+
+/// The bank API server, the web client shares its key
+pub struct Bank {
+    block_cipher: Box<dyn BlockCipher>,
+}
+
+impl Default for Bank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bank {
+    pub fn new() -> Self {
+        Bank {
+            block_cipher: aes(&random_128_bit()),
+        }
+    }
+
+    /// The web client of the first version, signs `from=<from>&to=<to>&amount=<amount>`
+    pub fn sign_transfer(&self, from: u32, to: u32, amount: u64) -> Vec<u8> {
+        let mut request = format!("from={}&to={}&amount={}", from, to, amount).into_bytes();
+        let iv = random_128_bit();
+        let mac = cbc_mac(self.block_cipher.as_ref(), &request, &iv);
+        request.extend(iv);
+        request.extend(mac);
+        request
+    }
+
+    /// Verify `message || iv || mac` and return the transfer `(from, to, amount)`
+    pub fn process_transfer(&self, request: &[u8]) -> Option<(u32, u32, u64)> {
+        if request.len() < 32 {
+            return None;
+        }
+        let (message, iv_mac) = request.split_at(request.len() - 32);
+        let (iv, mac) = iv_mac.split_at(16);
+        if !constant_time_eq(&cbc_mac(self.block_cipher.as_ref(), message, iv), mac) {
+            return None;
+        }
+        let params = parse_params(message);
+        Some((
+            params.get("from")?.parse().ok()?,
+            params.get("to")?.parse().ok()?,
+            params.get("amount")?.parse().ok()?,
+        ))
+    }
+
+    /// The web client of the second version, signs `from=<from>&tx_list=<to>:<amount>(;<to>:<amount>)*`
+    pub fn sign_transactions(&self, from: u32, transactions: &[(u32, u64)]) -> Vec<u8> {
+        let tx_list: Vec<String> = transactions
+            .iter()
+            .map(|(to, amount)| format!("{}:{}", to, amount))
+            .collect();
+        let mut request = format!("from={}&tx_list={}", from, tx_list.join(";")).into_bytes();
+        let mac = cbc_mac_fixed(self.block_cipher.as_ref(), &request);
+        request.extend(mac);
+        request
+    }
+
+    /// Verify `message || mac` and return the sender with all transactions that could be parsed
+    pub fn process_transactions(&self, request: &[u8]) -> Option<(u32, Vec<(u32, u64)>)> {
+        if request.len() < 16 {
+            return None;
+        }
+        let (message, mac) = request.split_at(request.len() - 16);
+        if !constant_time_eq(&cbc_mac_fixed(self.block_cipher.as_ref(), message), mac) {
+            return None;
+        }
+        // the transaction list is the last parameter and runs until the end
+        let message = String::from_utf8_lossy(message);
+        let (params, tx_list) = message.split_once("&tx_list=")?;
+        let params = parse_params(params.as_bytes());
+        let transactions = tx_list
+            .split(';')
+            .filter_map(|tx| {
+                let (to, amount) = tx.split_once(':')?;
+                Some((to.parse().ok()?, amount.parse().ok()?))
+            })
+            .collect();
+        Some((params.get("from")?.parse().ok()?, transactions))
+    }
+}
+
+/// The first occurrence of each `key=value`, invalid utf-8 becomes replacement characters
+fn parse_params(message: &[u8]) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    for pair in String::from_utf8_lossy(message).split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            params
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+    params
+}
+
+/// Replace `original` at the start of a signed `message || iv || mac` request with `forged`,
+/// fixing up the iv so the MAC stays valid. Both have to fit into the first block.
+pub fn forge_with_iv(request: &[u8], original: &[u8], forged: &[u8]) -> Vec<u8> {
+    assert_eq!(
+        original.len(),
+        forged.len(),
+        "replacement must keep the length"
+    );
+    assert!(original.len() <= 16, "only the first block can be changed");
+    assert!(
+        request.starts_with(original),
+        "request does not start with the original"
+    );
+    let mut request = request.to_vec();
+    let iv_start = request.len() - 32;
+    for (i, (o, f)) in original.iter().zip(forged.iter()).enumerate() {
+        request[i] = *f;
+        request[iv_start + i] ^= o ^ f;
+    }
+    request
+}
+
+/// Append our own signed `message || mac` request to a captured one, both with a zero iv.
+/// The result carries our MAC, the first block of our message turns into garbage.
+pub fn forge_by_extension(captured: &[u8], own: &[u8]) -> Vec<u8> {
+    assert!(
+        captured.len() >= 16 && own.len() >= 32,
+        "need a message and a mac"
+    );
+    let (message, mac) = captured.split_at(captured.len() - 16);
+    let mut forged = pad_pkcs7(message.to_vec(), 16);
+    forged.extend(own[..16].iter().zip(mac).map(|(b, m)| b ^ m));
+    forged.extend_from_slice(&own[16..]);
+    forged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VICTIM: u32 = 2;
+    const ATTACKER: u32 = 7;
+
+    #[test]
+    fn honest_requests() {
+        let bank = Bank::new();
+        let request = bank.sign_transfer(ATTACKER, VICTIM, 10);
+        assert_eq!(
+            bank.process_transfer(&request),
+            Some((ATTACKER, VICTIM, 10))
+        );
+        let mut tampered = request.clone();
+        tampered[5] = b'2';
+        assert_eq!(bank.process_transfer(&tampered), None);
+
+        let request = bank.sign_transactions(VICTIM, &[(3, 10), (4, 20)]);
+        assert_eq!(
+            bank.process_transactions(&request),
+            Some((VICTIM, vec![(3, 10), (4, 20)]))
+        );
+    }
+
+    #[test]
+    fn attacker_controlled_iv() {
+        let bank = Bank::new();
+        let own = bank.sign_transfer(ATTACKER, ATTACKER, 1_000_000);
+        let forged = forge_with_iv(&own, b"from=7", b"from=2");
+        assert_eq!(
+            bank.process_transfer(&forged),
+            Some((VICTIM, ATTACKER, 1_000_000))
+        );
+    }
+
+    #[test]
+    fn length_extension() {
+        let bank = Bank::new();
+        // captured on the wire
+        let captured = bank.sign_transactions(VICTIM, &[(3, 10), (4, 20)]);
+        // `from=7&tx_list=7` fills the first block, the rest ends up behind the garbage
+        let own = bank.sign_transactions(ATTACKER, &[(7, 1), (ATTACKER, 1_000_000)]);
+        let forged = forge_by_extension(&captured, &own);
+
+        let (from, transactions) = bank.process_transactions(&forged).unwrap();
+        assert_eq!(from, VICTIM);
+        assert_eq!(transactions[0], (3, 10));
+        assert_eq!(transactions.last(), Some(&(ATTACKER, 1_000_000)));
+    }
+}
@@ -0,0 +1,149 @@
+//! # Compression ratio side channel (CRIME)
+//!
+//! Requires:
+//! - requests that contain a secret (a session cookie) and data we control (the body)
+//! - compression before encryption, and the length of the ciphertext
+//!
+//! Idea: DEFLATE replaces repeated strings by back references.
+//! - a body `sessionid=<known><guess>` is a back reference to the cookie header, as long as the guess is right
+//! - a wrong guess costs an extra literal (8 bits), the right one extends the reference (0 or 1 bits)
+//! - try every character of the alphabet, the shortest request wins, repeat with the next position
+//!
+//! Lengths are only visible in whole bytes (CTR) or even whole blocks (CBC), so the 7 bits difference
+//! might be rounded away. The trick: put filler in front of the guess that does not compress,
+//! and make it just long enough that a guess known to be wrong crosses into the next byte or block.
+//! The right guess then falls back below that boundary, all wrong ones stay above.
+//! With the fixed Huffman codes, filler bytes below 144 cost 8 bits and the others 9 bits,
+//! so `a * 8 + b * 9` bits of filler can be any amount from 64 bits on.
+
+use crate::deflate::compress;
+use crate::{aes_cbc_encrypt, aes_ctr, pad_pkcs7, random_128_bit};
+
+/// The cookie from s6c51
+pub const SESSION_ID: &str = "TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=";
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+/// A guess that can never be right, it is not part of the alphabet
+const WRONG: u8 = b'~';
+
+/// Takes a request body and answers with the length of the encrypted request
+pub type LengthOracle = Box<dyn Fn(&[u8]) -> usize>;
+
+pub fn format_request(session_id: &str, body: &[u8]) -> Vec<u8> {
+    let mut request = format!(
+        "POST / HTTP/1.1\nHost: hapless.com\nCookie: sessionid={}\nContent-Length: {}\n",
+        session_id,
+        body.len()
+    )
+    .into_bytes();
+    request.extend_from_slice(body);
+    request
+}
+
+/// Compress the request and encrypt it with CTR under a fresh key, only the length is returned
+pub fn ctr_oracle(session_id: &'static str) -> LengthOracle {
+    Box::new(move |body| {
+        let compressed = compress(&format_request(session_id, body));
+        aes_ctr(&compressed, &random_128_bit(), rand::random()).len()
+    })
+}
+
+/// Compress the request and encrypt it with CBC under a fresh key and iv, only the length is returned
+pub fn cbc_oracle(session_id: &'static str) -> LengthOracle {
+    Box::new(move |body| {
+        let compressed = pad_pkcs7(compress(&format_request(session_id, body)), 16);
+        aes_cbc_encrypt(&compressed, &random_128_bit(), &random_128_bit()).len()
+    })
+}
+
+/// `bits` worth of filler bytes that appear nowhere else and never repeat, at least 64 bits
+fn filler(bits: usize) -> Vec<u8> {
+    assert!(bits >= 64);
+    let nine_bit = bits % 8;
+    let eight_bit = (bits - 9 * nine_bit) / 8;
+    // control characters except for the line break, then bytes that need 9 bit codes
+    let mut filler: Vec<u8> = (1..0x20u8)
+        .filter(|b| *b != b'\n')
+        .take(eight_bit)
+        .collect();
+    assert_eq!(filler.len(), eight_bit, "not enough filler bytes");
+    filler.extend(0x90..0x90 + nine_bit as u8);
+    filler
+}
+
+/// Find the filler that puts `guess` just across the next length boundary of the `oracle`
+fn calibrate(oracle: &dyn Fn(&[u8]) -> usize, guess: &[u8]) -> Option<Vec<u8>> {
+    let probe = |bits| {
+        let mut body = filler(bits);
+        body.extend_from_slice(guess);
+        oracle(&body)
+    };
+    let base = probe(64);
+    // a block of 16 bytes is the largest step to expect, plus some slack for the content length
+    (65..64 + 160).find(|bits| probe(*bits) > base).map(filler)
+}
+
+/// Recover the secret behind `prefix` (e.g. `sessionid=`) up to the line break,
+/// `None` if no guess stands out
+pub fn recover_secret(oracle: &dyn Fn(&[u8]) -> usize, prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut known = prefix.to_vec();
+    let mut candidates = BASE64.to_vec();
+    candidates.push(b'\n');
+    loop {
+        let mut wrong = known.clone();
+        wrong.push(WRONG);
+        let filler = calibrate(oracle, &wrong)?;
+        let lengths: Vec<(usize, u8)> = candidates
+            .iter()
+            .map(|c| {
+                let mut body = filler.clone();
+                body.extend_from_slice(&known);
+                body.push(*c);
+                (oracle(&body), *c)
+            })
+            .collect();
+        let shortest = lengths.iter().map(|(len, _)| *len).min()?;
+        let mut winners = lengths.iter().filter(|(len, _)| *len == shortest);
+        let (_, c) = *winners.next()?;
+        if winners.next().is_some() {
+            return None;
+        }
+        debug!("found {:?}", c as char);
+        if c == b'\n' {
+            return Some(known[prefix.len()..].to_vec());
+        }
+        known.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filler_bits() {
+        for bits in 64..64 + 160 {
+            let filler = filler(bits);
+            let cost: usize = filler.iter().map(|b| if *b < 144 { 8 } else { 9 }).sum();
+            assert_eq!(cost, bits);
+        }
+    }
+
+    #[test]
+    fn recover_with_ctr() {
+        let oracle = ctr_oracle(SESSION_ID);
+        assert_eq!(
+            recover_secret(oracle.as_ref(), b"sessionid="),
+            Some(SESSION_ID.as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn recover_with_cbc() {
+        let oracle = cbc_oracle(SESSION_ID);
+        assert_eq!(
+            recover_secret(oracle.as_ref(), b"sessionid="),
+            Some(SESSION_ID.as_bytes().to_vec())
+        );
+    }
+}
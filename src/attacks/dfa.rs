@@ -0,0 +1,178 @@
+//! # Differential fault analysis on AES-128 (Piret–Quisquater)
+//!
+//! Requires:
+//! - pairs of correct and faulty ciphertexts for the same plaintext
+//! - the fault flips bits of a single byte between `ShiftRows` and `MixColumns` of round 9
+//!
+//! Idea: a single faulty byte `f` is spread by `MixColumns` over its column,
+//! so the state difference after round 9 is `(2f, f, f, 3f)` (rotated, depending on the row of the fault).
+//! The last round has no `MixColumns`, so only 4 ciphertext bytes differ and for those
+//! `inv_sbox(c_i ^ k_i) ^ inv_sbox(c'_i ^ k_i) == m_i * f`.
+//! - for every fault value and row, each key byte can be checked on its own (4 * 256 instead of 2^32)
+//! - one pair leaves about 2^10 candidates for the 4 key bytes, intersecting with a second pair on
+//!   the same column usually leaves only the right one (a third pair takes care of the rest)
+//! - with all 4 columns of the last round key, the key schedule is inverted to the master key
+
+use std::collections::HashSet;
+
+use crate::aes::{gmul, inv_sbox, invert_key_schedule, MyAes, State, Step};
+
+/// A fault xors `value` onto the state byte `position` right before `MixColumns` of round 9
+#[derive(Clone, Copy, Debug)]
+pub struct Fault {
+    pub position: usize,
+    pub value: u8,
+}
+
+/// Encrypt a block with AES-128, but inject `fault` on the way
+pub fn encrypt_with_fault(aes: &MyAes, block: &[u8], fault: Fault) -> State {
+    let fault_round = aes.rounds() - 1;
+    aes.encrypt_block_observed(block, &mut |round, step, state| {
+        if round == fault_round && step == Step::ShiftRows {
+            state[fault.position] ^= fault.value;
+        }
+    })
+}
+
+/// Ciphertext positions that a fault in column `col` reaches, indexed by row
+fn affected_positions(col: usize) -> [usize; 4] {
+    // the last ShiftRows moves row r of column col into column (col - r) mod 4
+    let mut positions = [0; 4];
+    for (r, p) in positions.iter_mut().enumerate() {
+        *p = r + 4 * ((col + 4 - r) % 4);
+    }
+    positions
+}
+
+/// All candidates for the 4 last round key bytes covering `col`, using a single pair
+fn column_candidates(correct: &[u8], faulty: &[u8], col: usize) -> HashSet<[u8; 4]> {
+    let positions = affected_positions(col);
+    // for every row: difference before the last SubBytes -> key bytes producing it
+    let mut by_diff = vec![vec![Vec::new(); 256]; 4];
+    for (r, &p) in positions.iter().enumerate() {
+        for k in 0..=255u8 {
+            let diff = inv_sbox(correct[p] ^ k) ^ inv_sbox(faulty[p] ^ k);
+            by_diff[r][diff as usize].push(k);
+        }
+    }
+
+    let mut candidates = HashSet::new();
+    let mix_row = [2u8, 3, 1, 1];
+    for fault_row in 0..4 {
+        for f in 1..=255u8 {
+            let options: Vec<&Vec<u8>> = (0..4)
+                .map(|r| &by_diff[r][gmul(mix_row[(fault_row + 4 - r) % 4], f) as usize])
+                .collect();
+            if options.iter().any(|o| o.is_empty()) {
+                continue;
+            }
+            for &k0 in options[0] {
+                for &k1 in options[1] {
+                    for &k2 in options[2] {
+                        for &k3 in options[3] {
+                            candidates.insert([k0, k1, k2, k3]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// The column of round 9 a fault happened in, judging by the differing ciphertext bytes
+fn faulty_column(correct: &[u8], faulty: &[u8]) -> Option<usize> {
+    let differing: Vec<usize> = (0..16).filter(|&i| correct[i] != faulty[i]).collect();
+    (0..4).find(|&col| {
+        let mut positions = affected_positions(col).to_vec();
+        positions.sort_unstable();
+        positions == differing
+    })
+}
+
+/// Recover the AES-128 key from `(correct, faulty)` ciphertext pairs.
+/// Returns `None` if the pairs do not determine a single key yet.
+pub fn recover_key(pairs: &[(Vec<u8>, Vec<u8>)]) -> Option<Vec<u8>> {
+    let mut columns: Vec<Option<HashSet<[u8; 4]>>> = vec![None; 4];
+    for (correct, faulty) in pairs {
+        let col = match faulty_column(correct, faulty) {
+            Some(col) => col,
+            None => {
+                debug!("ignoring pair that does not look like a round 9 fault");
+                continue;
+            }
+        };
+        let new = column_candidates(correct, faulty, col);
+        columns[col] = Some(match columns[col].take() {
+            Some(old) => old.intersection(&new).cloned().collect(),
+            None => new,
+        });
+    }
+    debug!(
+        "candidates per column: {:?}",
+        columns
+            .iter()
+            .map(|c| c.as_ref().map(|c| c.len()))
+            .collect::<Vec<_>>()
+    );
+
+    let mut last_round_key = [0u8; 16];
+    for (col, candidates) in columns.iter().enumerate() {
+        let candidates = candidates.as_ref()?;
+        if candidates.len() != 1 {
+            return None;
+        }
+        let key_bytes = candidates.iter().next().unwrap();
+        for (&p, &k) in affected_positions(col).iter().zip(key_bytes.iter()) {
+            last_round_key[p] = k;
+        }
+    }
+    Some(invert_key_schedule(&last_round_key, 10))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcipher::BlockCipher;
+    use crate::random_128_bit;
+
+    fn faulty_pairs(aes: &MyAes, positions: &[usize]) -> Vec<(Vec<u8>, Vec<u8>)> {
+        positions
+            .iter()
+            .map(|&position| {
+                let plain = random_128_bit();
+                let fault = Fault {
+                    position,
+                    value: rand::random::<u8>() | 1,
+                };
+                (
+                    aes.encrypt_block(&plain),
+                    encrypt_with_fault(aes, &plain, fault).to_vec(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fault_touches_four_bytes() {
+        let aes = MyAes::new(&random_128_bit());
+        for position in 0..16 {
+            let (correct, faulty) = faulty_pairs(&aes, &[position]).pop().unwrap();
+            assert_eq!(faulty_column(&correct, &faulty), Some(position / 4));
+        }
+    }
+
+    #[test]
+    fn recovers_key_with_three_pairs_per_column() {
+        let key = random_128_bit();
+        let aes = MyAes::new(&key);
+        let pairs = faulty_pairs(&aes, &[0, 5, 10, 15, 1, 6, 11, 12, 2, 7, 8, 13]);
+        assert_eq!(recover_key(&pairs), Some(key));
+    }
+
+    #[test]
+    fn not_enough_pairs() {
+        let aes = MyAes::new(&random_128_bit());
+        assert_eq!(recover_key(&faulty_pairs(&aes, &[0, 5, 10])), None);
+    }
+}
@@ -0,0 +1,239 @@
+//! # GCM nonce reuse: the "forbidden attack"
+//!
+//! Requires:
+//! - two messages (aad, ciphertext, full tag) authenticated with the same key and nonce
+//!
+//! Idea: the tag is `GHASH_H(A, C) + E_k(J0)` and the mask `E_k(J0)` only depends on key and nonce.
+//! - adding both tags cancels the mask: `GHASH_H(A1, C1) + GHASH_H(A2, C2) + T1 + T2 = 0`
+//! - GHASH is a polynomial in `H` whose coefficients are the blocks we know,
+//!   so `H` is a root of a known polynomial over GF(2^128)
+//! - roots are found with Cantor–Zassenhaus: `gcd(f, X^(2^128) - X)` keeps only the linear factors,
+//!   which are split apart using the trace map `r + r^2 + r^4 + ... + r^(2^127)`
+//! - each candidate `H` yields the mask `T1 + GHASH_H(A1, C1)`, which lets us tag anything we like
+//!
+//! Just like the fixed nonce CTR in s3c19, the keystream repeats too,
+//! so the plaintext of a forged ciphertext can be chosen if one plaintext is known.
+
+use crate::gcm::{gf_inv, gf_mul, ghash, ghash_blocks};
+
+const ONE: u128 = 1 << 127;
+
+/// Coefficients over GF(2^128), lowest degree first and without trailing zeros
+type Poly = Vec<u128>;
+
+/// Everything an eavesdropper sees of a GCM message
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub aad: Vec<u8>,
+    pub cipher: Vec<u8>,
+    pub tag: Vec<u8>,
+}
+
+fn trim(mut p: Poly) -> Poly {
+    while p.last() == Some(&0) {
+        p.pop();
+    }
+    p
+}
+
+fn degree(p: &[u128]) -> Option<usize> {
+    p.len().checked_sub(1)
+}
+
+fn add(a: &[u128], b: &[u128]) -> Poly {
+    let mut sum = vec![0; a.len().max(b.len())];
+    for (i, c) in a.iter().enumerate() {
+        sum[i] ^= c;
+    }
+    for (i, c) in b.iter().enumerate() {
+        sum[i] ^= c;
+    }
+    trim(sum)
+}
+
+fn mul(a: &[u128], b: &[u128]) -> Poly {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![0; a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] ^= gf_mul(*x, *y);
+        }
+    }
+    trim(product)
+}
+
+/// Polynomial long division, returns `(quotient, remainder)`
+fn div_rem(a: &[u128], b: &[u128]) -> (Poly, Poly) {
+    let db = degree(b).expect("division by zero polynomial");
+    let lead_inv = gf_inv(b[db]);
+    let mut rem = a.to_vec();
+    let mut quot = vec![0; a.len().saturating_sub(db).max(1)];
+    while let Some(dr) = degree(&rem) {
+        if dr < db {
+            break;
+        }
+        let factor = gf_mul(rem[dr], lead_inv);
+        quot[dr - db] = factor;
+        for (i, c) in b.iter().enumerate() {
+            rem[dr - db + i] ^= gf_mul(factor, *c);
+        }
+        rem = trim(rem);
+    }
+    (trim(quot), rem)
+}
+
+fn rem(a: &[u128], b: &[u128]) -> Poly {
+    div_rem(a, b).1
+}
+
+fn monic(p: &[u128]) -> Poly {
+    let lead_inv = gf_inv(*p.last().expect("zero polynomial"));
+    p.iter().map(|c| gf_mul(*c, lead_inv)).collect()
+}
+
+fn gcd(a: &[u128], b: &[u128]) -> Poly {
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    while !b.is_empty() {
+        let r = rem(&a, &b);
+        a = b;
+        b = r;
+    }
+    monic(&a)
+}
+
+/// `r + r^2 + r^4 + ... + r^(2^127) mod f`
+fn trace(r: &[u128], f: &[u128]) -> Poly {
+    let mut square = rem(r, f);
+    let mut sum = square.clone();
+    for _ in 1..128 {
+        square = rem(&mul(&square, &square), f);
+        sum = add(&sum, &square);
+    }
+    sum
+}
+
+/// Split a product of distinct monic linear factors into its roots
+fn split_linear(f: &[u128], roots: &mut Vec<u128>) {
+    match degree(f) {
+        None | Some(0) => {}
+        // monic x + c has the root c (-c == c in characteristic 2)
+        Some(1) => roots.push(f[0]),
+        Some(d) => loop {
+            let r: Poly = trim((0..d).map(|_| rand::random()).collect());
+            let g = gcd(f, &trace(&r, f));
+            let dg = degree(&g).unwrap();
+            if dg > 0 && dg < d {
+                split_linear(&g, roots);
+                split_linear(&div_rem(f, &g).0, roots);
+                return;
+            }
+        },
+    }
+}
+
+/// All roots of `f` in GF(2^128)
+fn roots(f: &[u128]) -> Vec<u128> {
+    let f = monic(f);
+    // x^(2^128) mod f by repeated squaring
+    let x = vec![0, ONE];
+    let mut power = rem(&x, &f);
+    for _ in 0..128 {
+        power = rem(&mul(&power, &power), &f);
+    }
+    let linear = gcd(&f, &add(&power, &x));
+    let mut roots = Vec::new();
+    split_linear(&linear, &mut roots);
+    roots
+}
+
+/// GHASH of a message as a polynomial in `H` plus the tag as constant term
+fn tag_polynomial(message: &Message) -> Poly {
+    assert_eq!(message.tag.len(), 16, "needs the full tag");
+    let blocks = ghash_blocks(&message.aad, &message.cipher);
+    let mut poly = vec![u128::from_be_bytes(tag_array(&message.tag))];
+    // the first block is multiplied with the highest power
+    poly.extend(blocks.iter().rev());
+    poly
+}
+
+fn tag_array(tag: &[u8]) -> [u8; 16] {
+    let mut array = [0; 16];
+    array.copy_from_slice(tag);
+    array
+}
+
+/// Candidates for the authentication key `H` of two messages sharing key and nonce
+pub fn recover_auth_keys(first: &Message, second: &Message) -> Vec<u128> {
+    let f = add(&tag_polynomial(first), &tag_polynomial(second));
+    if f.is_empty() {
+        // identical messages, nothing to learn
+        return Vec::new();
+    }
+    roots(&f)
+}
+
+/// Compute the tag of a new message, given `H` and any message under the same key and nonce
+pub fn forge_tag(h: u128, known: &Message, aad: &[u8], cipher: &[u8]) -> Vec<u8> {
+    let mask = u128::from_be_bytes(tag_array(&known.tag)) ^ ghash(h, &known.aad, &known.cipher);
+    (ghash(h, aad, cipher) ^ mask).to_be_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcipher::aes;
+    use crate::gcm::{gcm_decrypt, gcm_encrypt};
+    use crate::{random_128_bit, xor, CryptoError};
+
+    fn encrypt(key: &[u8], nonce: &[u8], plain: &[u8], aad: &[u8]) -> Message {
+        let (cipher, tag) = gcm_encrypt(key, nonce, plain, aad, 16);
+        Message {
+            aad: aad.to_vec(),
+            cipher,
+            tag,
+        }
+    }
+
+    #[test]
+    fn polynomial_roots() {
+        let (a, b, c) = (rand::random(), rand::random(), rand::random());
+        // (x + a)(x + b)(x + c) * (x^3 + x + 1), the latter has no roots since 3 does not divide 128
+        let f = mul(
+            &mul(&mul(&[a, ONE], &[b, ONE]), &[c, ONE]),
+            &[ONE, ONE, 0, ONE],
+        );
+        let mut found = roots(&f);
+        found.sort_unstable();
+        let mut expected = vec![a, b, c];
+        expected.sort_unstable();
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn forbidden_attack() {
+        let key = random_128_bit();
+        let nonce = random_128_bit()[..12].to_vec();
+        let known_plain = b"transfer 100 to alice, thanks!".to_vec();
+        let first = encrypt(&key, &nonce, &known_plain, b"header");
+        let second = encrypt(&key, &nonce, b"some other message, not known", b"");
+
+        let candidates = recover_auth_keys(&first, &second);
+        let h = u128::from_be_bytes(tag_array(&aes(&key).encrypt_block(&[0; 16])));
+        assert!(candidates.contains(&h));
+
+        // reuse the keystream to choose the plaintext, then tag it
+        let evil_plain = b"transfer 999 to mallory, bye!!".to_vec();
+        let evil_cipher = xor(xor(evil_plain.clone(), &known_plain), &first.cipher);
+        let accepted: Vec<Result<Vec<u8>, CryptoError>> = candidates
+            .iter()
+            .map(|h| {
+                let tag = forge_tag(*h, &first, b"header", &evil_cipher);
+                gcm_decrypt(&key, &nonce, &evil_cipher, b"header", &tag, 16)
+            })
+            .filter(|r| r.is_ok())
+            .collect();
+        assert_eq!(accepted, vec![Ok(evil_plain)]);
+    }
+}
@@ -0,0 +1,104 @@
+//! # Length extension of secret prefix MACs (s4c29, s4c30)
+//!
+//! Requires:
+//! - a MAC `H(key || message)` with a Merkle–Damgård hash `H` that outputs its whole state
+//!   (SHA-1, MD4, SHA-256, SHA-512, not SHA-224 or SHA-384)
+//! - one message with its MAC, and an oracle that tells whether a message and MAC are valid
+//!
+//! Idea: the MAC is the chaining value after `key || message || padding`.
+//! - import it as the state of a hash that has already processed that many bytes
+//! - keep hashing the suffix, the result is the MAC of `key || message || padding || suffix`
+//! - the padding depends on the length of the key, try all key lengths until the oracle accepts
+
+use crate::digest::{Digest, State};
+
+/// Forge the MAC of `message || glue padding || suffix` for a key of `key_len` bytes,
+/// `None` if the hash does not output its whole state
+pub fn extend<D: Digest>(
+    message: &[u8],
+    mac: &D::Output,
+    key_len: usize,
+    suffix: &[u8],
+) -> Option<(Vec<u8>, D::Output)> {
+    let glue = D::padding(key_len + message.len());
+    let hashed = key_len + message.len() + glue.len();
+    let mut digest = D::import_state(State::from_output::<D>(mac, hashed as u64)?);
+    digest.update(suffix);
+    Some(([message, &glue, suffix].concat(), digest.finalize()))
+}
+
+/// Try key lengths up to `max_key_len` until the `oracle` accepts the extended message,
+/// returns the forged message and its MAC
+pub fn forge<D: Digest>(
+    message: &[u8],
+    mac: &D::Output,
+    suffix: &[u8],
+    max_key_len: usize,
+    oracle: &dyn Fn(&[u8], &D::Output) -> bool,
+) -> Option<(Vec<u8>, D::Output)> {
+    (0..=max_key_len).find_map(|key_len| {
+        let (forged, forged_mac) = extend::<D>(message, mac, key_len, suffix)?;
+        if oracle(&forged, &forged_mac) {
+            debug!("key length is {}", key_len);
+            Some((forged, forged_mac))
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md4::MyMd4;
+    use crate::md5::MyMd5;
+    use crate::sha1::MySha1;
+    use crate::sha2::{MySha224, MySha256, MySha512};
+
+    const MESSAGE: &[u8] =
+        b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+
+    /// Key lengths to try, with 35 and 43 the key and `MESSAGE` end exactly where the length
+    /// no longer fits into the block (112 mod 128 and 56 mod 64 bytes)
+    const KEY_LENGTHS: [usize; 5] = [1, 16, 35, 43, 64];
+
+    /// A server with a key of `key_len` bytes, returns the first MAC and the oracle
+    fn server<D: Digest>(key_len: usize) -> (D::Output, Box<dyn Fn(&[u8], &D::Output) -> bool>) {
+        let key: Vec<u8> = (0..key_len).map(|i| i as u8).collect();
+        let mac = move |message: &[u8]| D::digest(&[&key, message].concat());
+        (
+            mac(MESSAGE),
+            Box::new(move |message, tag| mac(message) == *tag),
+        )
+    }
+
+    fn extends<D: Digest>() {
+        for key_len in KEY_LENGTHS.iter() {
+            let (mac, oracle) = server::<D>(*key_len);
+            let (forged, forged_mac) =
+                forge::<D>(MESSAGE, &mac, b";admin=true", 64, oracle.as_ref())
+                    .expect("no key length worked");
+            assert!(forged.starts_with(MESSAGE));
+            assert!(forged.ends_with(b";admin=true"));
+            assert!(oracle(&forged, &forged_mac));
+        }
+    }
+
+    #[test]
+    fn every_full_state_hash() {
+        extends::<MySha1>();
+        extends::<MyMd4>();
+        extends::<MyMd5>();
+        extends::<MySha256>();
+        extends::<MySha512>();
+    }
+
+    #[test]
+    fn truncated_hash() {
+        let (mac, oracle) = server::<MySha224>(16);
+        assert_eq!(
+            forge::<MySha224>(MESSAGE, &mac, b";admin=true", 64, oracle.as_ref()),
+            None
+        );
+    }
+}
@@ -0,0 +1,13 @@
+//! # Attacks that are useful beyond a single challenge binary
+//! The blackboxes are passed in as closures, just like `detect_ecb` or `extract_fixed_suffix`.
+
+pub mod cbc_mac_collision;
+pub mod cbc_mac_forgery;
+pub mod compression_oracle;
+pub mod dfa;
+pub mod gcm_nonce_reuse;
+pub mod gcm_truncated_tag;
+pub mod length_extension;
+pub mod padding_oracle;
+pub mod poly1305_key_reuse;
+pub mod square;
@@ -0,0 +1,253 @@
+//! # CBC padding oracle
+//!
+//! Requires:
+//! - ciphertext + iv from cbc encryption with a known padding scheme
+//! - oracle that returns true if decrypting a ciphertext has a valid padding and can be queried repeatedly
+//!
+//! Idea: decrypt one block at a time, using the block in front of it (or the iv) as the thing to bitflip
+//! - by bitflipping the previous block, we can change the value of the last byte.
+//! - try out all bitflips until the oracle answers with true with bitflip `b`
+//! - we now know the last byte: `0x01 ^ b` (or whatever the padding needs the last byte to be)
+//! - for the next byte, set the known bytes to what a 2 byte padding needs and search again
+//! - repeat until every byte of the block is known
+//!
+//! A hit might complete a longer valid padding than we aimed for (the block ends on `02 02`,
+//! or `80 00` for ISO 7816-4). Flipping the byte in front of the guessed one breaks those, a real hit stays valid.
+//!
+//! Every block only needs itself and its predecessor, so all blocks are decrypted in parallel.
+//!
+//! ## Encrypting (CBC-R)
+//! Decrypting a block against an all zero previous block gives `D(c)`, the intermediate state.
+//! Whoever knows `D(c)` chooses what `c` decrypts to by picking the block in front of it: `c' = D(c) ^ p`.
+//! - start with a random last block and make the block in front of it decrypt it to the last plaintext block
+//! - that block is the next one to get the intermediate state of, going backwards until the first block
+//! - the block in front of the first one is the iv
+//!
+//! If the server does not take an iv, the forged iv is sent as a first block instead.
+//! Only that block decrypts to garbage, everything after it is exactly the chosen plaintext.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::padding::Padding;
+
+/// Answers if `cipher` decrypted with `iv` has a valid padding, has to be callable from several threads
+pub trait PaddingOracle: Sync {
+    fn check(&self, cipher: &[u8], iv: &[u8]) -> bool;
+}
+
+impl<F: Fn(&[u8], &[u8]) -> bool + Sync> PaddingOracle for F {
+    fn check(&self, cipher: &[u8], iv: &[u8]) -> bool {
+        self(cipher, iv)
+    }
+}
+
+/// The still padded plaintext and how many times the oracle was asked for it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Decryption {
+    pub plain: Vec<u8>,
+    pub queries: usize,
+}
+
+/// A forged `cipher` that decrypts to the chosen plaintext with `iv`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Encryption {
+    pub cipher: Vec<u8>,
+    pub iv: Vec<u8>,
+    pub queries: usize,
+}
+
+/// Counts the queries, even when shared between threads
+struct Counting<'a> {
+    oracle: &'a dyn PaddingOracle,
+    queries: AtomicUsize,
+}
+
+impl Counting<'_> {
+    fn check(&self, cipher: &[u8], iv: &[u8]) -> bool {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.oracle.check(cipher, iv)
+    }
+}
+
+/// Decrypt `block` by bitflipping `previous`, `None` if the padding does not single out every byte
+fn decrypt_block(
+    oracle: &Counting,
+    padding: &(dyn Padding + Sync),
+    previous: &[u8],
+    block: &[u8],
+) -> Option<Vec<u8>> {
+    let blocksize = block.len();
+    let mut decrypted = vec![0; blocksize];
+
+    for pos in (0..blocksize).rev() {
+        // the bytes the block has to end with, e.g. `03 03 03` for pkcs#7
+        let expected_padding = padding.oracle_suffix(blocksize - pos, blocksize)?;
+        let mut prev = previous.to_vec();
+        for known in pos + 1..blocksize {
+            // need to do c ^ b so that p ^ b = padding => b = p ^ padding
+            prev[known] ^= decrypted[known] ^ expected_padding[known - pos];
+        }
+        let mut byte = None;
+        for bitflip in 0..=255u8 {
+            prev[pos] = previous[pos] ^ bitflip;
+            if !oracle.check(block, &prev) {
+                continue;
+            }
+            if pos > 0 {
+                let mut check = prev.clone();
+                check[pos - 1] ^= 1;
+                if !oracle.check(block, &check) {
+                    continue;
+                }
+            }
+            byte = Some(bitflip ^ expected_padding[0]);
+            break;
+        }
+        decrypted[pos] = byte?;
+    }
+    Some(decrypted)
+}
+
+/// Decrypt the whole `cipher` with the help of the `oracle`, the padding is left in place.
+/// `None` if `padding` does not leak enough to recover a byte (ISO 10126 and zero padding).
+pub fn decrypt(
+    oracle: &dyn PaddingOracle,
+    padding: &(dyn Padding + Sync),
+    cipher: &[u8],
+    iv: &[u8],
+) -> Option<Decryption> {
+    // the iv is exactly one block, whatever the block cipher is
+    let blocksize = iv.len();
+    assert!(
+        !cipher.is_empty() && cipher.len().is_multiple_of(blocksize),
+        "expecting whole blocks"
+    );
+    let counting = Counting {
+        oracle,
+        queries: AtomicUsize::new(0),
+    };
+    let blocks: Option<Vec<Vec<u8>>> = (0..cipher.len() / blocksize)
+        .into_par_iter()
+        .map(|i| {
+            let previous = if i == 0 {
+                iv
+            } else {
+                &cipher[(i - 1) * blocksize..i * blocksize]
+            };
+            decrypt_block(
+                &counting,
+                padding,
+                previous,
+                &cipher[i * blocksize..(i + 1) * blocksize],
+            )
+        })
+        .collect();
+    Some(Decryption {
+        plain: blocks?.concat(),
+        queries: counting.queries.into_inner(),
+    })
+}
+
+/// Forge a ciphertext for `plain` (padded with `padding`) without knowing the key
+/// `None` if `padding` does not leak enough to recover the intermediate state.
+pub fn encrypt(
+    oracle: &dyn PaddingOracle,
+    padding: &(dyn Padding + Sync),
+    plain: &[u8],
+    blocksize: usize,
+) -> Option<Encryption> {
+    let counting = Counting {
+        oracle,
+        queries: AtomicUsize::new(0),
+    };
+    let plain = padding.pad(plain, blocksize);
+    let mut rng = rand::thread_rng();
+    let mut block: Vec<u8> = (0..blocksize).map(|_| rng.gen()).collect();
+    let mut blocks = vec![];
+    for plain_block in plain.chunks(blocksize).rev() {
+        let intermediate = decrypt_block(&counting, padding, &vec![0; blocksize], &block)?;
+        blocks.push(block);
+        block = intermediate
+            .iter()
+            .zip(plain_block.iter())
+            .map(|(i, p)| i ^ p)
+            .collect();
+    }
+    blocks.reverse();
+    Some(Encryption {
+        cipher: blocks.concat(),
+        iv: block,
+        queries: counting.queries.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcipher::aes;
+    use crate::padding::{AnsiX923, Iso10126, Iso7816, Pkcs7, Zero};
+    use crate::{cbc_decrypt, cbc_decrypt_padded, cbc_encrypt_padded, random_128_bit, unpad_pkcs7};
+
+    fn attack(padding: &(dyn Padding + Sync), plain: &[u8]) -> Option<Vec<u8>> {
+        let aes = aes(&random_128_bit());
+        let iv = random_128_bit();
+        let cipher = cbc_encrypt_padded(aes.as_ref(), plain, &iv, padding);
+        let oracle = |cipher: &[u8], iv: &[u8]| {
+            cbc_decrypt_padded(aes.as_ref(), cipher, iv, padding).is_ok()
+        };
+        let decrypted = decrypt(&oracle, padding, &cipher, &iv)?;
+        assert!(decrypted.queries > 0);
+        Some(padding.unpad(&decrypted.plain, 16).unwrap())
+    }
+
+    #[test]
+    fn decrypts_with_every_leaking_padding() {
+        let plain = b"With the bass kicked in and the Vega's are pumpin'".to_vec();
+        assert_eq!(attack(&Pkcs7, &plain), Some(plain.clone()));
+        assert_eq!(attack(&AnsiX923, &plain), Some(plain.clone()));
+        assert_eq!(attack(&Iso7816, &plain), Some(plain.clone()));
+        assert_eq!(attack(&Iso10126, &plain), None);
+        assert_eq!(attack(&Zero, &plain), None);
+    }
+
+    #[test]
+    fn block_that_looks_padded() {
+        // the bitflip for `02 02` comes before the one for `02 01`
+        let plain = b"YELLOW SUBMARI\x02\x03ICE ICE BABY".to_vec();
+        assert_eq!(attack(&Pkcs7, &plain), Some(plain.clone()));
+        let plain = b"YELLOW SUBMARI\x80\x01ICE ICE BABY".to_vec();
+        assert_eq!(attack(&Iso7816, &plain), Some(plain.clone()));
+    }
+
+    #[test]
+    fn forge_admin_token() {
+        // the s2c16 server: a fixed iv and only checking for the admin flag
+        let aes = aes(&random_128_bit());
+        let fixed_iv = random_128_bit();
+        let is_admin =
+            |cipher: &[u8]| match unpad_pkcs7(cbc_decrypt(aes.as_ref(), cipher, &fixed_iv)) {
+                Ok(plain) => String::from_utf8_lossy(&plain).contains(";admin=true;"),
+                Err(_) => false,
+            };
+        // with the s3c17 oracle shape, the iv is just another block to the server
+        let oracle = |cipher: &[u8], iv: &[u8]| {
+            let mut blocks = iv.to_vec();
+            blocks.extend_from_slice(cipher);
+            unpad_pkcs7(cbc_decrypt(aes.as_ref(), &blocks, &fixed_iv)).is_ok()
+        };
+
+        let plain = b"comment1=cooking%20MCs;userdata=;admin=true;".to_vec();
+        let forged = encrypt(&oracle, &Pkcs7, &plain, 16).unwrap();
+        assert_eq!(forged.cipher.len(), 48);
+        assert_eq!(
+            cbc_decrypt_padded(aes.as_ref(), &forged.cipher, &forged.iv, &Pkcs7),
+            Ok(plain)
+        );
+        let mut token = forged.iv;
+        token.extend(forged.cipher);
+        assert!(is_admin(&token));
+    }
+}
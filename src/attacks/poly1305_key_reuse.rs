@@ -0,0 +1,258 @@
+//! # Poly1305 with a reused one-time key
+//!
+//! Requires:
+//! - two different messages with their tags, computed with the same key `r || s`
+//!
+//! Idea: the tag is `(P(r) mod p + s) mod 2^128` where `P` is a polynomial with the message blocks as coefficients.
+//! - subtracting both tags cancels `s`, but the final `mod 2^128` leaves an unknown multiple of `2^128`:
+//!   `P1(r) - P2(r) = T1 - T2 + k * 2^128 (mod p)` with `k` in `-4..=4`, since `P(r) mod p + s < 5 * 2^128`
+//! - for each `k` that is a known polynomial over GF(2^130 - 5) and `r` is one of its roots
+//! - roots are found with Cantor–Zassenhaus: `gcd(f, X^p - X)` keeps only the linear factors,
+//!   which are split apart with `gcd(f, (X + a)^((p-1)/2) - 1)` for random `a`
+//! - clamping zeroes 22 bits of `r`, which rules out almost all wrong roots,
+//!   and each remaining `r` gives `s = T1 - P1(r) mod 2^128`, checked against the second tag
+//!
+//! The same structure as the GCM forbidden attack, just over a prime field instead of GF(2^128).
+
+use num_bigint::{BigUint, RandBigInt};
+use rand::thread_rng;
+
+use crate::poly1305::{blocks, clamp, p, to_tag};
+
+/// Coefficients mod p, lowest degree first and without trailing zeros
+type Poly = Vec<BigUint>;
+
+/// A message and its Poly1305 tag
+#[derive(Clone, Debug)]
+pub struct Message {
+    pub data: Vec<u8>,
+    pub tag: Vec<u8>,
+}
+
+fn zero() -> BigUint {
+    BigUint::from(0u8)
+}
+
+fn one() -> BigUint {
+    BigUint::from(1u8)
+}
+
+fn trim(mut f: Poly) -> Poly {
+    while f.last() == Some(&zero()) {
+        f.pop();
+    }
+    f
+}
+
+fn degree(f: &[BigUint]) -> Option<usize> {
+    f.len().checked_sub(1)
+}
+
+fn inv(x: &BigUint, p: &BigUint) -> BigUint {
+    assert_ne!(*x, zero(), "zero has no inverse");
+    x.modpow(&(p - 2u8), p)
+}
+
+fn sub(a: &[BigUint], b: &[BigUint], p: &BigUint) -> Poly {
+    let mut diff = vec![zero(); a.len().max(b.len())];
+    for (i, c) in a.iter().enumerate() {
+        diff[i] = c.clone();
+    }
+    for (i, c) in b.iter().enumerate() {
+        diff[i] = (&diff[i] + p - c) % p;
+    }
+    trim(diff)
+}
+
+fn mul(a: &[BigUint], b: &[BigUint], p: &BigUint) -> Poly {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![zero(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate() {
+        for (j, y) in b.iter().enumerate() {
+            product[i + j] = (&product[i + j] + x * y) % p;
+        }
+    }
+    trim(product)
+}
+
+/// Polynomial long division, returns `(quotient, remainder)`
+fn div_rem(a: &[BigUint], b: &[BigUint], p: &BigUint) -> (Poly, Poly) {
+    let db = degree(b).expect("division by zero polynomial");
+    let lead_inv = inv(&b[db], p);
+    let mut rem = a.to_vec();
+    let mut quot = vec![zero(); a.len().saturating_sub(db).max(1)];
+    while let Some(dr) = degree(&rem) {
+        if dr < db {
+            break;
+        }
+        let factor = &rem[dr] * &lead_inv % p;
+        for (i, c) in b.iter().enumerate() {
+            rem[dr - db + i] = (&rem[dr - db + i] + p - &factor * c % p) % p;
+        }
+        quot[dr - db] = factor;
+        rem = trim(rem);
+    }
+    (trim(quot), rem)
+}
+
+fn rem(a: &[BigUint], b: &[BigUint], p: &BigUint) -> Poly {
+    div_rem(a, b, p).1
+}
+
+fn monic(f: &[BigUint], p: &BigUint) -> Poly {
+    let lead_inv = inv(f.last().expect("zero polynomial"), p);
+    f.iter().map(|c| c * &lead_inv % p).collect()
+}
+
+fn gcd(a: &[BigUint], b: &[BigUint], p: &BigUint) -> Poly {
+    let (mut a, mut b) = (a.to_vec(), b.to_vec());
+    while !b.is_empty() {
+        let r = rem(&a, &b, p);
+        a = b;
+        b = r;
+    }
+    monic(&a, p)
+}
+
+/// `base^e mod f` by square and multiply
+fn pow_mod(base: &[BigUint], e: &BigUint, f: &[BigUint], p: &BigUint) -> Poly {
+    let mut result = vec![one()];
+    let base = rem(base, f, p);
+    for i in (0..e.bits()).rev() {
+        result = rem(&mul(&result, &result, p), f, p);
+        if e.bit(i) {
+            result = rem(&mul(&result, &base, p), f, p);
+        }
+    }
+    result
+}
+
+/// Split a product of distinct monic linear factors into its roots
+fn split_linear(f: &[BigUint], p: &BigUint, roots: &mut Vec<BigUint>) {
+    match degree(f) {
+        None | Some(0) => {}
+        // monic x + c has the root -c
+        Some(1) => roots.push((p - &f[0]) % p),
+        Some(d) => loop {
+            let a = thread_rng().gen_biguint_below(p);
+            let half = pow_mod(&[a, one()], &((p - 1u8) >> 1), f, p);
+            let g = gcd(f, &sub(&half, &[one()], p), p);
+            let dg = degree(&g).unwrap();
+            if dg > 0 && dg < d {
+                split_linear(&g, p, roots);
+                split_linear(&div_rem(f, &g, p).0, p, roots);
+                return;
+            }
+        },
+    }
+}
+
+/// All roots of `f` in GF(p)
+fn roots(f: &[BigUint], p: &BigUint) -> Vec<BigUint> {
+    let f = monic(f, p);
+    let x = vec![zero(), one()];
+    let power = pow_mod(&x, p, &f, p);
+    let linear = gcd(&f, &sub(&power, &x, p), p);
+    let mut roots = Vec::new();
+    split_linear(&linear, p, &mut roots);
+    roots
+}
+
+/// The Poly1305 accumulator as polynomial in `r`: `c_1 * r^q + ... + c_q * r`
+fn message_polynomial(data: &[u8], p: &BigUint) -> Poly {
+    let mut poly = vec![zero()];
+    // the first block is multiplied with the highest power
+    poly.extend(blocks(data).into_iter().rev().map(|c| c % p));
+    trim(poly)
+}
+
+fn evaluate(f: &[BigUint], x: &BigUint, p: &BigUint) -> BigUint {
+    f.iter().rev().fold(zero(), |acc, c| (acc * x + c) % p)
+}
+
+/// Candidates `(r, s)` for the one-time key shared by two different messages
+pub fn recover_keys(first: &Message, second: &Message) -> Vec<(BigUint, BigUint)> {
+    let p = p();
+    let two_128 = one() << 128;
+    let t1 = BigUint::from_bytes_le(&first.tag);
+    let t2 = BigUint::from_bytes_le(&second.tag);
+    let p1 = message_polynomial(&first.data, &p);
+    let difference = sub(&p1, &message_polynomial(&second.data, &p), &p);
+    if degree(&difference).unwrap_or(0) == 0 {
+        // same polynomial, nothing to learn
+        return Vec::new();
+    }
+
+    let mut candidates = Vec::new();
+    for k in -4i8..=4 {
+        // P1 - P2 - (T1 - T2 + k * 2^128) mod p, kept non-negative by adding 4 * 2^128 < p on both sides
+        let offset = (&t1 + &two_128 * (4 + k) as u8) % &p;
+        let constant = (&t2 + &two_128 * 4u8) % &p;
+        let f = sub(&difference, &[(&offset + &p - &constant) % &p], &p);
+        for r in roots(&f, &p) {
+            if r.bits() > 128 || clamp(&to_tag(&r)) != r {
+                continue;
+            }
+            let s = (&t1 + &two_128 * 4u8 - evaluate(&p1, &r, &p)) % &two_128;
+            if forge_tag(&r, &s, &second.data) == second.tag
+                && !candidates.contains(&(r.clone(), s.clone()))
+            {
+                candidates.push((r, s));
+            }
+        }
+    }
+    candidates
+}
+
+/// Tag any message with a recovered key
+pub fn forge_tag(r: &BigUint, s: &BigUint, data: &[u8]) -> Vec<u8> {
+    let p = p();
+    to_tag(&(evaluate(&message_polynomial(data, &p), r, &p) + s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::poly1305::poly1305;
+    use crate::random_128_bit;
+
+    #[test]
+    fn polynomial_roots() {
+        let p = p();
+        let (a, b) = (BigUint::from(1234u32), BigUint::from(98765u32));
+        // (x - a)(x - b)(x^2 + 1), the latter has no roots since p = 3 mod 4
+        let f = mul(
+            &mul(&[&p - &a, one()], &[&p - &b, one()], &p),
+            &[one(), zero(), one()],
+            &p,
+        );
+        let mut found = roots(&f, &p);
+        found.sort();
+        assert_eq!(found, vec![a, b]);
+    }
+
+    #[test]
+    fn key_reuse_forgery() {
+        let key = [random_128_bit(), random_128_bit()].concat();
+        let verify = |data: &[u8], tag: &[u8]| poly1305(&key, data) == tag;
+
+        let sign = |data: &[u8]| Message {
+            data: data.to_vec(),
+            tag: poly1305(&key, data),
+        };
+        let first = sign(b"transfer 100 to alice, thanks!");
+        let second = sign(b"some other message, also sent with the same key");
+
+        let candidates = recover_keys(&first, &second);
+        assert!(candidates.contains(&(clamp(&key[..16]), BigUint::from_bytes_le(&key[16..]))));
+
+        let evil = b"transfer 999 to mallory, bye!!";
+        let accepted: Vec<_> = candidates
+            .iter()
+            .filter(|(r, s)| verify(evil, &forge_tag(r, s, evil)))
+            .collect();
+        assert!(!accepted.is_empty());
+    }
+}
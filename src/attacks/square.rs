@@ -0,0 +1,106 @@
+//! # Square (integral) attack on 4 round AES
+//!
+//! Requires:
+//! - an encryption blackbox for 4 round AES (the last round without `MixColumns`)
+//! - the ability to choose plaintexts, 256 per Λ-set
+//!
+//! Idea: take a Λ-set, 256 plaintexts that differ only in one byte, which takes every possible value.
+//! - after one round, that byte is still active (takes every value) and everything else constant
+//! - `MixColumns` of the second round spreads the active byte over its column
+//! - after the third round every byte of the state is active, but `MixColumns` is linear,
+//!   so at the end of the third round the xor over all 256 states is zero for every byte (balanced)
+//!
+//! The ciphertext byte `c_j` is `SBOX[s_j'] ^ k_j`, where `s_j'` is the state byte before the last
+//! `SubBytes`/`ShiftRows`. So for every guess of `k_j` we can compute `s_j'`, and a right guess has to
+//! produce a balanced byte. A wrong guess survives with probability 1/256, so a few Λ-sets suffice.
+//! Every key byte is guessed independently: 16 * 256 guesses instead of 2^128.
+//! Once the last round key is known, the key schedule can be inverted to get the master key.
+
+use std::collections::HashSet;
+
+use crate::aes::{inv_sbox, invert_key_schedule, State};
+
+/// Number of Λ-sets after which we give up on narrowing down the candidates
+const MAX_SETS: usize = 16;
+
+/// Recover the round key of the 4th (last) round.
+pub fn recover_last_round_key(blackbox: &mut dyn Fn(Vec<u8>) -> Vec<u8>) -> Option<State> {
+    let mut candidates: Vec<HashSet<u8>> = (0..16).map(|_| (0..=255u8).collect()).collect();
+    for _ in 0..MAX_SETS {
+        let constant: u8 = rand::random();
+        let ciphers: Vec<Vec<u8>> = (0..=255u8)
+            .map(|active| {
+                let mut plain = vec![constant; 16];
+                plain[0] = active;
+                blackbox(plain)
+            })
+            .collect();
+        for (j, candidates_j) in candidates.iter_mut().enumerate() {
+            candidates_j.retain(|&guess| {
+                ciphers
+                    .iter()
+                    .fold(0, |acc, c| acc ^ inv_sbox(c[j] ^ guess))
+                    == 0
+            });
+        }
+        debug!(
+            "candidates per byte: {:?}",
+            candidates.iter().map(|c| c.len()).collect::<Vec<_>>()
+        );
+        if candidates.iter().any(|c| c.is_empty()) {
+            // not a 4 round AES
+            return None;
+        }
+        if candidates.iter().all(|c| c.len() == 1) {
+            let mut key = [0; 16];
+            for (k, c) in key.iter_mut().zip(candidates.iter()) {
+                *k = *c.iter().next().unwrap();
+            }
+            return Some(key);
+        }
+    }
+    None
+}
+
+/// Recover the AES-128 master key of a 4 round AES blackbox
+pub fn square_attack(blackbox: &mut dyn Fn(Vec<u8>) -> Vec<u8>) -> Option<Vec<u8>> {
+    recover_last_round_key(blackbox).map(|k4| invert_key_schedule(&k4, 4))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aes::MyAes;
+    use crate::blockcipher::BlockCipher;
+    use crate::random_128_bit;
+
+    #[test]
+    fn balanced_after_three_rounds() {
+        let aes = MyAes::with_rounds(&random_128_bit(), 4);
+        let mut sum = [0u8; 16];
+        for active in 0..=255u8 {
+            let mut plain = vec![0x42; 16];
+            plain[0] = active;
+            let states = aes.encrypt_states(&plain);
+            for (s, b) in sum.iter_mut().zip(states[3].iter()) {
+                *s ^= b;
+            }
+        }
+        assert_eq!(sum, [0; 16]);
+    }
+
+    #[test]
+    fn recovers_key() {
+        let key = random_128_bit();
+        let aes = MyAes::with_rounds(&key, 4);
+        let mut blackbox = |plain: Vec<u8>| aes.encrypt_block(&plain);
+        assert_eq!(square_attack(&mut blackbox), Some(key));
+    }
+
+    #[test]
+    fn fails_on_more_rounds() {
+        let aes = MyAes::with_rounds(&random_128_bit(), 5);
+        let mut blackbox = |plain: Vec<u8>| aes.encrypt_block(&plain);
+        assert_eq!(square_attack(&mut blackbox), None);
+    }
+}
@@ -36,14 +36,26 @@
 //! ```
 //! So if we give the `is_admin` logic the malicious `iv' = iv ^ b_iv`, the resulting plaintext looks like the original except that `p_2` has been replaced by `m_2`.
 //! This could probably also be extended for changing multiple blocks
+//! With a padding oracle instead of error messages, `attacks::padding_oracle::encrypt` does this for every block (CBC-R).
+//!
+//! ## Other modes
+//! The same flip in the previous block does not work everywhere:
+//! - PCBC feeds the garbled plaintext forward, so every block after the flip is garbled as well
+//! - CFB flips the bits in the *same* block and garbles the next one, the attack needs to target the block itself
 extern crate rand;
 
 use std::iter::FromIterator;
 
-use cyptopals::{aes_cbc_decrypt, aes_cbc_encrypt, pad_pkcs7, random_128_bit, unpad_pkcs7, xor};
+use cyptopals::blockcipher::aes;
+use cyptopals::{
+    cbc_decrypt, cbc_encrypt, cfb_decrypt, cfb_encrypt, pad_pkcs7, pcbc_decrypt, pcbc_encrypt,
+    random_128_bit, unpad_pkcs7, xor,
+};
+
+type Mode<'a> = dyn Fn(&[u8]) -> Vec<u8> + 'a;
 
 /// Encrypt a profile, but don't allow the email to contain '&' or '='
-fn encrypt(mut userdata: Vec<u8>, key: &Vec<u8>, iv: &Vec<u8>) -> Vec<u8> {
+fn encrypt(mut userdata: Vec<u8>, mode: &Mode) -> Vec<u8> {
     let mut plain = b"comment1=cooking%20MCs;userdata=".to_vec();
     println!("prefix lenght: {}", plain.len());
     userdata = userdata
@@ -55,31 +67,61 @@ fn encrypt(mut userdata: Vec<u8>, key: &Vec<u8>, iv: &Vec<u8>) -> Vec<u8> {
     println!("prefix+user lenght: {}", plain.len());
     plain.extend(b";comment2=%20like%20a%20pound%20of%20bacon".to_vec());
     println!("prefix+user+suffix lenght: {}", plain.len());
-    aes_cbc_encrypt(&pad_pkcs7(plain, 16), &key, &iv.to_owned())
+    mode(&pad_pkcs7(plain, 16))
 }
 
-fn is_admin(cipher: Vec<u8>, key: &Vec<u8>, iv: &Vec<u8>) -> bool {
-    let decrypted = unpad_pkcs7(aes_cbc_decrypt(&cipher, key, iv)).unwrap();
+fn is_admin(cipher: Vec<u8>, mode: &Mode) -> bool {
+    let decrypted = match unpad_pkcs7(mode(&cipher)) {
+        Ok(decrypted) => decrypted,
+        Err(e) => {
+            // PCBC garbles everything up to the padding
+            println!("decryption failed: {:?}", e);
+            return false;
+        }
+    };
     let ascii = String::from_iter(decrypted.iter().map(|b| *b as char));
     println!("decrypted: {:?}", ascii);
     ascii.contains(";admin=true;")
 }
 
-fn main() {
+fn flip_previous_block(encrypt_mode: &Mode, decrypt_mode: &Mode) -> bool {
     let blocksize = 16;
-    let key = random_128_bit();
-    let iv = random_128_bit();
 
     let user_plain = b"0123456789012345".to_vec();
     let intended_plain = b"AAAAA;admin=true".to_vec();
     let bitflips = xor(user_plain.clone(), &intended_plain);
-    let mut cipher = encrypt(user_plain, &key, &iv);
+    let mut cipher = encrypt(user_plain, encrypt_mode);
 
     let range_previous_block = blocksize * 1..blocksize * 2;
     let previous_block = cipher.get(range_previous_block.clone()).unwrap().to_vec();
     let replacement = xor(previous_block, &bitflips);
     cipher.splice(range_previous_block, replacement);
 
-    let admin = is_admin(cipher, &key, &iv);
-    println!("we are admin: {}", admin);
+    is_admin(cipher, decrypt_mode)
+}
+
+fn main() {
+    let aes = aes(&random_128_bit());
+    let iv = random_128_bit();
+    let modes: Vec<(&str, Box<Mode<'_>>, Box<Mode<'_>>)> = vec![
+        (
+            "CBC",
+            Box::new(|plain: &[u8]| cbc_encrypt(aes.as_ref(), plain, &iv)),
+            Box::new(|cipher: &[u8]| cbc_decrypt(aes.as_ref(), cipher, &iv)),
+        ),
+        (
+            "PCBC",
+            Box::new(|plain: &[u8]| pcbc_encrypt(aes.as_ref(), plain, &iv)),
+            Box::new(|cipher: &[u8]| pcbc_decrypt(aes.as_ref(), cipher, &iv)),
+        ),
+        (
+            "CFB",
+            Box::new(|plain: &[u8]| cfb_encrypt(aes.as_ref(), plain, &iv)),
+            Box::new(|cipher: &[u8]| cfb_decrypt(aes.as_ref(), cipher, &iv)),
+        ),
+    ];
+    for (name, encrypt_mode, decrypt_mode) in modes.iter() {
+        let admin = flip_previous_block(encrypt_mode.as_ref(), decrypt_mode.as_ref());
+        println!("{}: we are admin: {}", name, admin);
+    }
 }
@@ -4,25 +4,30 @@
 //! - ciphertext + iv from cbc encryption
 //! - oracle that returns true if decrypting a ciphertext has a valid padding and can be queried repeatedly
 //!
-//! Idea: Go backwards through the ciphertext, guessing one byte at a time
-//! - by bitflipping the previous block, we can change the value of the last byte.
-//! - try out all bitflips until the oracle answers with true with bitflip `b`
-//! - we now know the last byte: `0x01 ^ b`
-//! - for the next byte, choose a bitflip that changes the last byte to `0x02`
-//! - repeat the previous process until the oracle returns a true, then the second to last byte is `0x02 ^ b'`
-//! - repeat until every byte is known, discarding blocks from matching once all bytes have been found
+//! The attack itself lives in `attacks::padding_oracle`, see there for how it works.
+//! It decrypts the blocks in parallel and counts how often it had to ask the oracle.
 //!
-//! Possible edgecase and improvements (from research):
-//! - the block already has valid padding (ends on `02 02`) we have 2 possible valid found bytes: `02` and `01`.
-//!   can be caught by checking if block by its own already returns a valid padding
-//! - we only need to take 2 consecutive blocks to decrypt the second one. This should make the decryption operations faster
+//! ## Other paddings
+//! Nothing about this is specific to pkcs#7, we only need to know which bytes the oracle accepts
+//! at the end of a block (`Padding::oracle_suffix`): `00 00 03` for ANSI X.923, `80 00 00` for ISO 7816-4.
+//! ISO 10126 does not check the filler and accepts any length byte, zero padding only rejects blocks of zeros.
+//! Both still leak something, but not enough to pin down a single byte.
+//!
+//! ## Ciphertext stealing
+//! CBC with ciphertext stealing needs no padding, so every ciphertext of at least one block decrypts without error.
+//! The server has nothing left to complain about and the oracle always answers true, the attack only produces garbage.
+//! (It is still unauthenticated CBC, bitflipping like in s2c16 works just the same.)
 extern crate rand;
 
-use core::mem;
-
-use cyptopals::{aes_cbc_decrypt, aes_cbc_encrypt, pad_pkcs7, random_128_bit, unpad_pkcs7};
+use cyptopals::attacks::padding_oracle;
+use cyptopals::blockcipher::aes;
+use cyptopals::padding::{self, Padding};
+use cyptopals::{
+    cbc_cs_decrypt, cbc_cs_encrypt, cbc_decrypt_padded, cbc_encrypt_padded, random_128_bit,
+    CiphertextStealing,
+};
 
-fn get_oracle() -> (Vec<u8>, Vec<u8>, Box<dyn Fn(&Vec<u8>, &Vec<u8>) -> bool>) {
+fn random_plain() -> Vec<u8> {
     let options = vec![
         base64::decode("MDAwMDAwTm93IHRoYXQgdGhlIHBhcnR5IGlzIGp1bXBpbmc="),
         base64::decode(
@@ -44,87 +49,77 @@ fn get_oracle() -> (Vec<u8>, Vec<u8>, Box<dyn Fn(&Vec<u8>, &Vec<u8>) -> bool>) {
         .unwrap();
     println!("plain: {:?}", String::from_utf8(plain.clone()).unwrap());
     println!("pos  : 0123456789012345");
-    let key = random_128_bit();
+    plain
+}
+
+/// The attacker knows which padding the server uses
+fn get_oracle(
+    padding: &'static (dyn Padding + Sync),
+) -> (Vec<u8>, Vec<u8>, Box<dyn Fn(&[u8], &[u8]) -> bool + Sync>) {
+    let plain = random_plain();
+    let aes = aes(&random_128_bit());
     let iv = random_128_bit();
-    let cipher = aes_cbc_encrypt(&pad_pkcs7(plain, 16), &key, &iv);
+    let cipher = cbc_encrypt_padded(aes.as_ref(), &plain, &iv, padding);
     (
         cipher,
         iv,
         Box::new(move |cipher, iv| {
-            let result = aes_cbc_decrypt(&cipher, &key, iv);
+            let result = cbc_decrypt_padded(aes.as_ref(), cipher, iv, padding);
             //        println!("{:?}", result);
-            let result = unpad_pkcs7(result);
             result.is_ok()
         }),
     )
 }
 
-fn cbc_padding_oracle(
-    oracle: &mut dyn Fn(&Vec<u8>, &Vec<u8>) -> bool,
-    previous_block: &Vec<u8>,
-    to_decrypt: &Vec<u8>,
-) -> Vec<u8> {
-    let blocksize = 16;
-    let mut decrypted = vec![0; blocksize];
-
-    for pos in (0usize..blocksize).rev() {
-        let expected_padding = (blocksize - pos) as u8;
-        //        println!("padding {:?}", expected_padding);
-
-        let mut prev = previous_block.clone();
-        let current_byte = prev.get(pos).unwrap().to_owned(); // to be bitflipped
-        for known in pos + 1..blocksize {
-            // need to do c ^ b so that p ^ b = padding => b = p ^ padding
-            let prev_byte = *prev.get(known).unwrap();
-            mem::swap(
-                prev.get_mut(known).unwrap(),
-                &mut (prev_byte ^ decrypted[known] ^ expected_padding),
-            );
-        }
-        let mut byte = None;
-        for bitflip in 0..255u8 {
-            mem::swap(prev.get_mut(pos).unwrap(), &mut (current_byte ^ bitflip));
-            // don't guess '1' without bitflips, since it could also be another valid padding
-            if oracle(to_decrypt, &prev) && !(bitflip == 0 && bitflip ^ expected_padding == 1) {
-                byte = Some(bitflip ^ expected_padding);
-                //                println!("with bitflip {:?}", bitflip);
-                //                println!("found byte {:?}", byte.unwrap());
-                break;
+/// The same server using CBC-CS3, it only fails if the ciphertext is too short
+fn get_cts_oracle() -> (Vec<u8>, Vec<u8>, Box<dyn Fn(&[u8], &[u8]) -> bool + Sync>) {
+    let plain = random_plain();
+    let aes = aes(&random_128_bit());
+    let iv = random_128_bit();
+    let cipher = cbc_cs_encrypt(aes.as_ref(), &plain, &iv, CiphertextStealing::Cs3);
+    (
+        cipher,
+        iv,
+        Box::new(move |cipher, iv| {
+            if cipher.len() < 16 {
+                return false;
             }
-        }
-        //        println!("found byte {:?}", byte.unwrap() as char);
-        decrypted[pos] = byte.unwrap_or(1); // I excluded 1 earlier, but it can be a valid option
-    }
-
-    //    println!("{:?}", decrypted.clone());
-    //    println!("{:?}", String::from_utf8(decrypted.clone()));
-    decrypted
-}
-
-fn cbc_padding_oracle_multi(
-    cipher: &Vec<u8>,
-    iv: &Vec<u8>,
-    oracle: &mut Box<dyn Fn(&Vec<u8>, &Vec<u8>) -> bool>,
-) -> Vec<u8> {
-    let mut decrypted = Vec::new();
-    decrypted.extend(cbc_padding_oracle(oracle, &iv, &cipher[0..16].to_vec()));
-    for block in 1..(cipher.len() / 16) {
-        decrypted.extend(cbc_padding_oracle(
-            oracle,
-            &cipher[(block - 1) * 16..block * 16].to_vec(),
-            &cipher[block * 16..(block + 1) * 16].to_vec(),
-        ));
-    }
-    decrypted
+            // nothing to verify, whatever comes out is the plaintext
+            cbc_cs_decrypt(aes.as_ref(), cipher, iv, CiphertextStealing::Cs3);
+            true
+        }),
+    )
 }
 
 fn main() {
-    let (cipher, iv, mut oracle) = get_oracle();
-    println!("oracle works: {}", oracle(&cipher, &iv));
+    let schemes: Vec<(&str, &'static (dyn Padding + Sync))> = vec![
+        ("pkcs#7", &padding::Pkcs7),
+        ("ansi x.923", &padding::AnsiX923),
+        ("iso 10126", &padding::Iso10126),
+        ("iso 7816-4", &padding::Iso7816),
+        ("zero", &padding::Zero),
+    ];
+    for (name, scheme) in schemes {
+        let (cipher, iv, oracle) = get_oracle(scheme);
+        println!("oracle works: {}", oracle(&cipher, &iv));
+
+        match padding_oracle::decrypt(&oracle, scheme, &cipher, &iv) {
+            Some(decrypted) => println!(
+                "{}: {:?} after {} queries",
+                name,
+                String::from_utf8(scheme.unpad(&decrypted.plain, 16).unwrap()),
+                decrypted.queries
+            ),
+            None => println!("{}: no oracle to work with", name),
+        }
+    }
 
-    let decrypted = cbc_padding_oracle_multi(&cipher, &iv, &mut oracle);
+    let (cipher, iv, oracle) = get_cts_oracle();
+    // the stolen part of the last block is not a block of its own
+    let whole_blocks = &cipher[..cipher.len() / 16 * 16];
+    let decrypted = padding_oracle::decrypt(&oracle, &padding::Pkcs7, whole_blocks, &iv).unwrap();
     println!(
-        "{:?}",
-        String::from_utf8(unpad_pkcs7(decrypted.clone()).unwrap())
+        "with ciphertext stealing: {:?}",
+        String::from_utf8_lossy(&decrypted.plain)
     );
 }
@@ -4,9 +4,12 @@
 //! The decryption gets worse the less ciphers there are for that position, but it is good enough.
 //! Improvements would be exploiting the statistics of the english language more.
 //! But the challenge says, this is the inferior solution, so lets just move on.
+//!
+//! Nothing here is specific to AES-CTR, a fixed nonce breaks ChaCha20 (or any stream cipher) the same way.
+use cyptopals::chacha20::chacha20;
 use cyptopals::{aes_ctr, break_xor_single_byte, random_128_bit};
 
-fn get_ciphers() -> Vec<Vec<u8>> {
+fn get_ciphers(encrypt: &dyn Fn(&Vec<u8>) -> Vec<u8>) -> Vec<Vec<u8>> {
     let plains = vec![
         base64::decode("SSBoYXZlIG1ldCB0aGVtIGF0IGNsb3NlIG9mIGRheQ==").unwrap(),
         base64::decode("Q29taW5nIHdpdGggdml2aWQgZmFjZXM=").unwrap(),
@@ -50,13 +53,10 @@ fn get_ciphers() -> Vec<Vec<u8>> {
         base64::decode("QSB0ZXJyaWJsZSBiZWF1dHkgaXMgYm9ybi4=").unwrap(),
     ];
 
-    let key = random_128_bit();
-    let ciphers = plains.iter().map(|p| aes_ctr(p, &key, 0)).collect();
-    ciphers
+    plains.iter().map(|p| encrypt(p)).collect()
 }
 
-fn main() {
-    let ciphers = get_ciphers();
+fn decrypt_fixed_nonce(ciphers: Vec<Vec<u8>>) {
     let mut keystream = Vec::new();
     for i in 0..64 {
         let cipher = ciphers.iter().flat_map(|c| c.get(i)).map(|c| *c).collect();
@@ -76,3 +76,14 @@ fn main() {
         println!("{}: {}", i, decrypted);
     }
 }
+
+fn main() {
+    let key = random_128_bit();
+    println!("AES-CTR:");
+    decrypt_fixed_nonce(get_ciphers(&|p| aes_ctr(p, &key, 0)));
+
+    let key = [random_128_bit(), random_128_bit()].concat();
+    let nonce = [0; 12];
+    println!("ChaCha20:");
+    decrypt_fixed_nonce(get_ciphers(&|p| chacha20(p, &key, &nonce, 0)));
+}
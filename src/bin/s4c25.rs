@@ -7,17 +7,34 @@
 //! 2) only that one byte is changed.
 //! A useful encryption would need another encryption primitive operating on plaintext(/-derivatives)
 //! to include a non-trivially reversible component.
+//!
+//! The attack never looks at the cipher, so ChaCha20 falls the same way as AES-CTR.
+//!
+//! ## XTS
+//! Disk encryption uses XTS instead: every 16 byte block position has its own permutation, no keystream.
+//! The byte-wise attack only produces garbage (and corrupts the disk while reverting its "changes").
+//! What remains:
+//! - a write only changes the ciphertext of its own block, so the attacker sees which blocks are touched
+//! - it is deterministic, writing a guessed block and comparing the ciphertext confirms the guess
 use std::fs;
 
+use cyptopals::chacha20::chacha20;
+use cyptopals::xts::Xts;
 use cyptopals::{aes_ctr, aes_ecb_decrypt, random_128_bit};
 
-fn edit(cipher: &Vec<u8>, key: &Vec<u8>, offset: usize, new: &Vec<u8>) -> Vec<u8> {
+/// `stream` en-/decrypts with a fixed key and nonce
+fn edit(
+    cipher: &Vec<u8>,
+    stream: &dyn Fn(&Vec<u8>) -> Vec<u8>,
+    offset: usize,
+    new: &Vec<u8>,
+) -> Vec<u8> {
     assert!(offset + new.len() <= cipher.len());
-    let mut plain = aes_ctr(&cipher, &key, 0);
+    let mut plain = stream(&cipher);
     &plain.splice(offset..offset + new.len(), new.iter().cloned());
-    // we can hardcode the nonce to 0, since it is valid for the whole ciphertext
+    // we can hardcode the nonce, since it is valid for the whole ciphertext
     // and the text says nothing about rotating (which implies reencrypting everything)
-    aes_ctr(&plain, &key, 0)
+    stream(&plain)
 }
 
 /// Slow and steady: decrypt one byte at a time
@@ -49,13 +66,53 @@ fn break_random_rw_ctr(
     plain
 }
 
+/// Write a guess for a whole block, the ciphertext stays the same iff it was right
+fn confirm_guess(
+    cipher: &Vec<u8>,
+    apicall: &mut dyn Fn(&Vec<u8>, usize, &Vec<u8>) -> Vec<u8>,
+    offset: usize,
+    guess: &Vec<u8>,
+) -> bool {
+    apicall(cipher, offset, guess) == *cipher
+}
+
 fn main() {
     let line = fs::read_to_string("./files/aes_ecb.txt").unwrap();
     let cipher = base64::decode(&line.replace("\n", "")).unwrap();
 
     let plain = aes_ecb_decrypt(&cipher, b"YELLOW SUBMARINE".to_vec().as_ref());
     let key = random_128_bit();
-    let cipher = aes_ctr(&plain, &key, 0);
-    let mut api = |cipher: &Vec<u8>, offset: usize, new: &Vec<u8>| edit(cipher, &key, offset, new);
-    assert_eq!(break_random_rw_ctr(cipher, &mut api), plain);
+    let stream = |data: &Vec<u8>| aes_ctr(data, &key, 0);
+    let mut api =
+        |cipher: &Vec<u8>, offset: usize, new: &Vec<u8>| edit(cipher, &stream, offset, new);
+    assert_eq!(break_random_rw_ctr(stream(&plain), &mut api), plain);
+
+    let key = [random_128_bit(), random_128_bit()].concat();
+    let nonce = random_128_bit()[..12].to_vec();
+    let stream = |data: &Vec<u8>| chacha20(data, &key, &nonce, 0);
+    let mut api =
+        |cipher: &Vec<u8>, offset: usize, new: &Vec<u8>| edit(cipher, &stream, offset, new);
+    assert_eq!(break_random_rw_ctr(stream(&plain), &mut api), plain);
+
+    let xts = Xts::new(&[random_128_bit(), random_128_bit()].concat());
+    let mut api =
+        |cipher: &Vec<u8>, offset: usize, new: &Vec<u8>| xts.edit(cipher, 512, offset, new);
+    let cipher = xts.encrypt(&plain, 512);
+    println!();
+    println!(
+        "XTS: {:?}",
+        String::from_utf8_lossy(&break_random_rw_ctr(cipher.clone(), &mut api)[..32])
+    );
+    assert!(confirm_guess(
+        &cipher,
+        &mut api,
+        0,
+        &b"I'm back and I'm".to_vec()
+    ));
+    assert!(!confirm_guess(
+        &cipher,
+        &mut api,
+        0,
+        &b"I'm back and you".to_vec()
+    ));
 }
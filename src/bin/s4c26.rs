@@ -3,11 +3,22 @@
 //! Works even better than cbc, since wie don't destroy the previous block with the bitflip.
 //! Apart from that, the attack is the same, guess/know the offset of my plaintext, calculate the controlled bitflips and apply them.
 //! Only had to change the encryption functions and the offset.
-use cyptopals::{aes_ctr, random_128_bit, xor};
+//!
+//! ## Other modes
+//! - OFB is a keystream just like CTR, the flip works the same
+//! - CFB flips the bits in place, but the changed cipher block garbles the next plaintext block
+//!   (here the `;` after our `admin=true`)
+//! - CFB-8 feeds every cipher byte back, so only the last flipped byte of the block survives ungarbled
+use cyptopals::blockcipher::aes;
+use cyptopals::{
+    aes_ctr, cfb8_decrypt, cfb8_encrypt, cfb_decrypt, cfb_encrypt, ofb, random_128_bit, xor,
+};
 use std::iter::FromIterator;
 
+type Mode<'a> = dyn Fn(&[u8]) -> Vec<u8> + 'a;
+
 /// Encrypt a profile, but don't allow the email to contain '&' or '='
-fn encrypt(mut userdata: Vec<u8>, key: &Vec<u8>) -> Vec<u8> {
+fn encrypt(mut userdata: Vec<u8>, mode: &Mode) -> Vec<u8> {
     let mut plain = b"comment1=cooking%20MCs;userdata=".to_vec();
     println!("prefix lenght: {}", plain.len());
     userdata = userdata
@@ -19,31 +30,60 @@ fn encrypt(mut userdata: Vec<u8>, key: &Vec<u8>) -> Vec<u8> {
     println!("prefix+user lenght: {}", plain.len());
     plain.extend(b";comment2=%20like%20a%20pound%20of%20bacon".to_vec());
     println!("prefix+user+suffix lenght: {}", plain.len());
-    aes_ctr(&plain, &key, 0)
+    mode(&plain)
 }
 
-fn is_admin(cipher: Vec<u8>, key: &Vec<u8>) -> bool {
-    let decrypted = aes_ctr(&cipher, &key, 0);
+fn is_admin(cipher: Vec<u8>, mode: &Mode) -> bool {
+    let decrypted = mode(&cipher);
     let ascii = String::from_iter(decrypted.iter().map(|b| *b as char));
     println!("decrypted: {:?}", ascii);
     ascii.contains(";admin=true;")
 }
 
-fn main() {
-    let key = random_128_bit();
-
+fn flip_in_place(encrypt_mode: &Mode, decrypt_mode: &Mode) -> bool {
     let user_plain = b"0123456789012345".to_vec();
     let intended_plain = b"AAAAA;admin=true".to_vec();
     let plain_len = user_plain.len();
     let magic_offset = 16 * 2;
     let bitflips = xor(user_plain.clone(), &intended_plain);
-    let mut cipher = encrypt(user_plain, &key);
+    let mut cipher = encrypt(user_plain, encrypt_mode);
 
     let range_previous_block = magic_offset..magic_offset + plain_len;
     let previous_block = cipher.get(range_previous_block.clone()).unwrap().to_vec();
     let replacement = xor(previous_block, &bitflips);
     cipher.splice(range_previous_block, replacement);
 
-    let admin = is_admin(cipher, &key);
-    println!("we are admin: {}", admin);
+    is_admin(cipher, decrypt_mode)
+}
+
+fn main() {
+    let key = random_128_bit();
+    let aes = aes(&key);
+    let iv = random_128_bit();
+    let modes: Vec<(&str, Box<Mode<'_>>, Box<Mode<'_>>)> = vec![
+        (
+            "CTR",
+            Box::new(|plain: &[u8]| aes_ctr(plain, &key, 0)),
+            Box::new(|cipher: &[u8]| aes_ctr(cipher, &key, 0)),
+        ),
+        (
+            "OFB",
+            Box::new(|plain: &[u8]| ofb(aes.as_ref(), plain, &iv)),
+            Box::new(|cipher: &[u8]| ofb(aes.as_ref(), cipher, &iv)),
+        ),
+        (
+            "CFB",
+            Box::new(|plain: &[u8]| cfb_encrypt(aes.as_ref(), plain, &iv)),
+            Box::new(|cipher: &[u8]| cfb_decrypt(aes.as_ref(), cipher, &iv)),
+        ),
+        (
+            "CFB-8",
+            Box::new(|plain: &[u8]| cfb8_encrypt(aes.as_ref(), plain, &iv)),
+            Box::new(|cipher: &[u8]| cfb8_decrypt(aes.as_ref(), cipher, &iv)),
+        ),
+    ];
+    for (name, encrypt_mode, decrypt_mode) in modes.iter() {
+        let admin = flip_in_place(encrypt_mode.as_ref(), decrypt_mode.as_ref());
+        println!("{}: we are admin: {}", name, admin);
+    }
 }
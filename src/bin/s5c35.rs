@@ -19,10 +19,10 @@ use std::thread;
 use num_bigint::BigUint;
 
 use cyptopals::dh::{begin_dh, begin_dh_nist};
-use cyptopals::sha1::MySha1;
+use cyptopals::kdf::hkdf;
+use cyptopals::sha2::MySha256;
 use cyptopals::{
-    aes_cbc_decrypt, aes_cbc_encrypt, pad_pkcs7, random_128_bit, u32_be_bytes, unpad_pkcs7,
-    CryptoError,
+    aes_cbc_decrypt, aes_cbc_encrypt, pad_pkcs7, random_128_bit, unpad_pkcs7, CryptoError,
 };
 
 use crate::Messages::{DhFinish, DhSetup, EncryptedMessage};
@@ -44,7 +44,7 @@ enum Messages {
 }
 
 fn gen_key(value: BigUint) -> Vec<u8> {
-    u32_be_bytes(&MySha1::hash(MySha1::pad(value.to_bytes_be()))[0..4])
+    hkdf::<MySha256>(b"", &value.to_bytes_be(), b"aes-128-cbc key", 16)
 }
 
 fn encrypt(plain: Vec<u8>, key: &Vec<u8>, iv: &Vec<u8>) -> Vec<u8> {
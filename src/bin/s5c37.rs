@@ -20,28 +20,29 @@ use std::thread;
 
 use num_bigint::BigUint;
 
+use cyptopals::digest::Digest;
 use cyptopals::sha1::MySha1;
 use cyptopals::srp::{client_ext, get_hmac, n, server, Messages};
 use std::ops::Mul;
 
 fn client_1(tx: Sender<Messages>, rx: Receiver<Messages>) {
     let a_source = |_: &BigUint, _: &BigUint, _: &BigUint| BigUint::from(0u8);
-    let shared_k = MySha1::hash_padded(BigUint::from(0u8).to_bytes_be());
-    let hmac_source = |s: &[u8], _: &[u32]| get_hmac(s, &shared_k);
+    let shared_k = MySha1::digest(&BigUint::from(0u8).to_bytes_be());
+    let hmac_source = |s: &[u8], _: &[u8]| get_hmac(s, &shared_k);
     client_ext(tx, rx, &a_source, &hmac_source)
 }
 
 fn client_2(tx: Sender<Messages>, rx: Receiver<Messages>) {
     let a_source = |_: &BigUint, _: &BigUint, _: &BigUint| n();
-    let shared_k = MySha1::hash_padded(BigUint::from(0u8).to_bytes_be());
-    let hmac_source = |s: &[u8], _: &[u32]| get_hmac(s, &shared_k);
+    let shared_k = MySha1::digest(&BigUint::from(0u8).to_bytes_be());
+    let hmac_source = |s: &[u8], _: &[u8]| get_hmac(s, &shared_k);
     client_ext(tx, rx, &a_source, &hmac_source)
 }
 
 fn client_3(tx: Sender<Messages>, rx: Receiver<Messages>) {
     let a_source = |_: &BigUint, _: &BigUint, _: &BigUint| n().mul(2u8);
-    let shared_k = MySha1::hash_padded(BigUint::from(0u8).to_bytes_be());
-    let hmac_source = |s: &[u8], _: &[u32]| get_hmac(s, &shared_k);
+    let shared_k = MySha1::digest(&BigUint::from(0u8).to_bytes_be());
+    let hmac_source = |s: &[u8], _: &[u8]| get_hmac(s, &shared_k);
     client_ext(tx, rx, &a_source, &hmac_source)
 }
 
@@ -0,0 +1,128 @@
+//! # CBC-MAC and CMAC (RFC 4493)
+//! CBC-MAC is the last block of a CBC encryption of the (pkcs#7 padded) message.
+//! It is only secure for messages of a fixed length with a fixed iv:
+//! - an attacker chosen iv flips bits in the first block without changing the MAC
+//! - the MAC is the chaining value, so whoever knows one can continue the chain with another message
+//!
+//! See `attacks::cbc_mac_forgery` for both, and `attacks::cbc_mac_collision` for why a public key makes it no hash either.
+//! CMAC fixes the length extension by xoring one of two derived subkeys into the last block before encrypting it.
+
+use crate::blockcipher::BlockCipher;
+use crate::{cbc_encrypt, pad_pkcs7};
+
+/// `x^128 = x^7 + x^2 + x + 1`, as used for the CMAC subkeys
+const RB: u128 = 0x87;
+
+/// CBC-MAC with a variable iv, the iv has to be sent along with the MAC
+pub fn cbc_mac(block_cipher: &dyn BlockCipher, message: &[u8], iv: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    let cipher = cbc_encrypt(
+        block_cipher,
+        &pad_pkcs7(message.to_vec(), blocksize as u8),
+        iv,
+    );
+    cipher[cipher.len() - blocksize..].to_vec()
+}
+
+/// CBC-MAC with the iv fixed to zero
+pub fn cbc_mac_fixed(block_cipher: &dyn BlockCipher, message: &[u8]) -> Vec<u8> {
+    cbc_mac(block_cipher, message, &vec![0; block_cipher.block_size()])
+}
+
+/// Multiply by `x` in GF(2^128), big endian as in RFC 4493
+fn double(block: u128) -> u128 {
+    if block >> 127 == 1 {
+        (block << 1) ^ RB
+    } else {
+        block << 1
+    }
+}
+
+/// The two subkeys `K1` (for complete last blocks) and `K2` (for padded ones)
+pub fn cmac_subkeys(block_cipher: &dyn BlockCipher) -> (u128, u128) {
+    let mut l = [0; 16];
+    l.copy_from_slice(&block_cipher.encrypt_block(&[0; 16]));
+    let k1 = double(u128::from_be_bytes(l));
+    (k1, double(k1))
+}
+
+/// AES-CMAC, only defined for a 16 byte block cipher
+pub fn cmac(block_cipher: &dyn BlockCipher, message: &[u8]) -> Vec<u8> {
+    assert_eq!(block_cipher.block_size(), 16, "CMAC needs 128 bit blocks");
+    let (k1, k2) = cmac_subkeys(block_cipher);
+    let complete = !message.is_empty() && message.len().is_multiple_of(16);
+    let last_start = if complete {
+        message.len() - 16
+    } else {
+        message.len() / 16 * 16
+    };
+    let mut last = [0; 16];
+    last[..message.len() - last_start].copy_from_slice(&message[last_start..]);
+    let last = if complete {
+        u128::from_be_bytes(last) ^ k1
+    } else {
+        // bit padding, a single 1 bit followed by zeros
+        last[message.len() - last_start] = 0x80;
+        u128::from_be_bytes(last) ^ k2
+    };
+
+    let mut state = 0u128;
+    for chunk in message[..last_start].chunks(16) {
+        let mut block = [0; 16];
+        block.copy_from_slice(chunk);
+        state = encrypt(block_cipher, state ^ u128::from_be_bytes(block));
+    }
+    encrypt(block_cipher, state ^ last).to_be_bytes().to_vec()
+}
+
+fn encrypt(block_cipher: &dyn BlockCipher, block: u128) -> u128 {
+    let mut out = [0; 16];
+    out.copy_from_slice(&block_cipher.encrypt_block(&block.to_be_bytes()));
+    u128::from_be_bytes(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcipher::aes;
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    #[test]
+    fn cbc_mac_is_last_cbc_block() {
+        let aes = aes(b"YELLOW SUBMARINE");
+        let iv = [1; 16];
+        let message = b"ICE ICE BABY, ICE ICE BABY";
+        let cipher = cbc_encrypt(aes.as_ref(), &pad_pkcs7(message.to_vec(), 16), &iv);
+        assert_eq!(cbc_mac(aes.as_ref(), message, &iv), cipher[16..].to_vec());
+        assert_eq!(
+            cbc_mac_fixed(aes.as_ref(), message),
+            cbc_mac(aes.as_ref(), message, &[0; 16])
+        );
+    }
+
+    /// RFC 4493 section 4
+    #[test]
+    fn cmac_rfc_4493() {
+        let aes = aes(&h("2b7e151628aed2a6abf7158809cf4f3c"));
+        let (k1, k2) = cmac_subkeys(aes.as_ref());
+        assert_eq!(k1, 0xfbeed618357133667c85e08f7236a8de);
+        assert_eq!(k2, 0xf7ddac306ae266ccf90bc11ee46d513b);
+
+        let message = h(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        );
+        let vectors = [
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            (40, "dfa66747de9ae63030ca32611497c827"),
+            (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+        ];
+        for (len, tag) in vectors.iter() {
+            assert_eq!(cmac(aes.as_ref(), &message[..*len]), h(tag));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! # ChaCha20 and ChaCha20-Poly1305 (RFC 8439)
+//! A stream cipher built from add-rotate-xor on a 4x4 matrix of 32 bit words:
+//! constants, 256 bit key, 32 bit block counter and 96 bit nonce.
+//! Like CTR, every keystream block depends only on (key, nonce, counter),
+//! so everything that breaks a reused CTR keystream breaks ChaCha20 just the same.
+//!
+//! The AEAD uses the first keystream block (counter 0) as one-time Poly1305 key
+//! and encrypts starting with counter 1.
+
+use crate::poly1305::poly1305;
+use crate::{constant_time_eq, CryptoError};
+
+/// "expand 32-byte k"
+const CONSTANTS: [u32; 4] = [0x6170_7865, 0x3320_646e, 0x7962_2d32, 0x6b20_6574];
+
+fn quarter_round(state: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(16);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(12);
+    state[a] = state[a].wrapping_add(state[b]);
+    state[d] = (state[d] ^ state[a]).rotate_left(8);
+    state[c] = state[c].wrapping_add(state[d]);
+    state[b] = (state[b] ^ state[c]).rotate_left(7);
+}
+
+fn words(bytes: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    bytes
+        .chunks(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+}
+
+/// One 64 byte keystream block
+pub fn chacha20_block(key: &[u8], counter: u32, nonce: &[u8]) -> Vec<u8> {
+    assert_eq!(key.len(), 32, "invalid key length");
+    assert_eq!(nonce.len(), 12, "invalid nonce length");
+    let mut initial = [0u32; 16];
+    initial[..4].copy_from_slice(&CONSTANTS);
+    for (i, word) in words(key).enumerate() {
+        initial[4 + i] = word;
+    }
+    initial[12] = counter;
+    for (i, word) in words(nonce).enumerate() {
+        initial[13 + i] = word;
+    }
+
+    let mut state = initial;
+    for _ in 0..10 {
+        // column rounds
+        quarter_round(&mut state, 0, 4, 8, 12);
+        quarter_round(&mut state, 1, 5, 9, 13);
+        quarter_round(&mut state, 2, 6, 10, 14);
+        quarter_round(&mut state, 3, 7, 11, 15);
+        // diagonal rounds
+        quarter_round(&mut state, 0, 5, 10, 15);
+        quarter_round(&mut state, 1, 6, 11, 12);
+        quarter_round(&mut state, 2, 7, 8, 13);
+        quarter_round(&mut state, 3, 4, 9, 14);
+    }
+    state
+        .iter()
+        .zip(initial.iter())
+        .flat_map(|(s, i)| s.wrapping_add(*i).to_le_bytes().to_vec())
+        .collect()
+}
+
+/// Encrypt or decrypt `data`, the keystream starts at block `counter`
+pub fn chacha20(data: &[u8], key: &[u8], nonce: &[u8], counter: u32) -> Vec<u8> {
+    data.chunks(64)
+        .enumerate()
+        .flat_map(|(i, chunk)| {
+            let keystream = chacha20_block(key, counter.wrapping_add(i as u32), nonce);
+            chunk
+                .iter()
+                .zip(keystream)
+                .map(|(x, y)| x ^ y)
+                .collect::<Vec<u8>>()
+        })
+        .collect()
+}
+
+fn pad16(data: &mut Vec<u8>) {
+    let padding = (16 - data.len() % 16) % 16;
+    data.extend(vec![0; padding]);
+}
+
+/// `aad || pad || cipher || pad || len(aad) || len(cipher)`
+fn aead_tag(key: &[u8], nonce: &[u8], cipher: &[u8], aad: &[u8]) -> Vec<u8> {
+    let otk = chacha20_block(key, 0, nonce)[..32].to_vec();
+    let mut mac_data = aad.to_vec();
+    pad16(&mut mac_data);
+    mac_data.extend_from_slice(cipher);
+    pad16(&mut mac_data);
+    mac_data.extend_from_slice(&(aad.len() as u64).to_le_bytes());
+    mac_data.extend_from_slice(&(cipher.len() as u64).to_le_bytes());
+    poly1305(&otk, &mac_data)
+}
+
+/// Encrypt and authenticate, returns `(ciphertext, tag)`
+pub fn chacha20_poly1305_encrypt(
+    key: &[u8],
+    nonce: &[u8],
+    plain: &[u8],
+    aad: &[u8],
+) -> (Vec<u8>, Vec<u8>) {
+    let cipher = chacha20(plain, key, nonce, 1);
+    let tag = aead_tag(key, nonce, &cipher, aad);
+    (cipher, tag)
+}
+
+/// Verify the tag and only then decrypt
+pub fn chacha20_poly1305_decrypt(
+    key: &[u8],
+    nonce: &[u8],
+    cipher: &[u8],
+    aad: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let expected = aead_tag(key, nonce, cipher, aad);
+    if !constant_time_eq(&expected, tag) {
+        return Err(CryptoError::Authentication);
+    }
+    Ok(chacha20(cipher, key, nonce, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUNSCREEN: &[u8] = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    /// RFC 8439 section 2.3.2
+    #[test]
+    fn block_function() {
+        let key: Vec<u8> = (0..32).collect();
+        assert_eq!(
+            hex::encode(chacha20_block(&key, 1, &h("000000090000004a00000000"))),
+            "10f1e7e4d13b5915500fdd1fa32071c4c7d1f4c733c068030422aa9ac3d46c4e\
+             d2826446079faa0914c2d705d98b02a2b5129cd1de164eb9cbd083e8a2503c4e"
+        );
+    }
+
+    /// RFC 8439 section 2.4.2
+    #[test]
+    fn encryption() {
+        let key: Vec<u8> = (0..32).collect();
+        let nonce = h("000000000000004a00000000");
+        let cipher = chacha20(SUNSCREEN, &key, &nonce, 1);
+        assert_eq!(
+            hex::encode(&cipher),
+            "6e2e359a2568f98041ba0728dd0d6981e97e7aec1d4360c20a27afccfd9fae0b\
+             f91b65c5524733ab8f593dabcd62b3571639d624e65152ab8f530c359f0861d8\
+             07ca0dbf500d6a6156a38e088a22b65e52bc514d16ccf806818ce91ab7793736\
+             5af90bbf74a35be6b40b8eedf2785e42874d"
+        );
+        assert_eq!(chacha20(&cipher, &key, &nonce, 1), SUNSCREEN.to_vec());
+    }
+
+    /// RFC 8439 section 2.8.2
+    #[test]
+    fn aead() {
+        let key = h("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f");
+        let nonce = h("070000004041424344454647");
+        let aad = h("50515253c0c1c2c3c4c5c6c7");
+        let (cipher, tag) = chacha20_poly1305_encrypt(&key, &nonce, SUNSCREEN, &aad);
+        assert_eq!(
+            hex::encode(&cipher),
+            "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6\
+             3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36\
+             92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc\
+             3ff4def08e4b7a9de576d26586cec64b6116"
+        );
+        assert_eq!(hex::encode(&tag), "1ae10b594f09e26a7e902ecbd0600691");
+        assert_eq!(
+            chacha20_poly1305_decrypt(&key, &nonce, &cipher, &aad, &tag),
+            Ok(SUNSCREEN.to_vec())
+        );
+
+        let mut forged = cipher.clone();
+        forged[0] ^= 1;
+        assert_eq!(
+            chacha20_poly1305_decrypt(&key, &nonce, &forged, &aad, &tag),
+            Err(CryptoError::Authentication)
+        );
+        assert_eq!(
+            chacha20_poly1305_decrypt(&key, &nonce, &cipher, b"other aad", &tag),
+            Err(CryptoError::Authentication)
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! # A minimal DEFLATE (RFC 1951)
+//! Just enough compression for the compression side channel (s6c51 style, see `attacks::compression_oracle`):
+//! - LZ77: greedily replace repeated strings (3 to 258 bytes) by `(length, distance)` back references
+//!   into the last 32 KiB
+//! - Huffman: the fixed codes of the specification, so no code tables have to be sent.
+//!   Literals below 144 cost 8 bits, the others 9 bits, a back reference 12 to 31 bits.
+//!
+//! The output is a single final block, readable by any inflate implementation.
+//! `decompress` only handles stored and fixed Huffman blocks, not dynamic ones.
+
+use std::collections::HashMap;
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+
+/// Base length and extra bits of length codes 257 to 285
+const LENGTHS: [(u16, u8); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+
+/// Base distance and extra bits of distance codes 0 to 29
+const DISTANCES: [(u16, u8); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Token {
+    Literal(u8),
+    Match { length: usize, distance: usize },
+}
+
+/// Greedy LZ77, always taking the longest (and then closest) match
+pub fn lz77(data: &[u8]) -> Vec<Token> {
+    let mut tokens = vec![];
+    // every position a 3 byte prefix was seen at, newest last
+    let mut seen: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let mut best = (0, 0);
+        if pos + MIN_MATCH <= data.len() {
+            if let Some(starts) = seen.get(&data[pos..pos + MIN_MATCH]) {
+                for &start in starts.iter().rev() {
+                    if pos - start > WINDOW {
+                        break;
+                    }
+                    let length = data[pos..]
+                        .iter()
+                        .zip(data[start..].iter())
+                        .take(MAX_MATCH)
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    if length > best.0 {
+                        best = (length, pos - start);
+                    }
+                }
+            }
+        }
+        let step = if best.0 >= MIN_MATCH {
+            tokens.push(Token::Match {
+                length: best.0,
+                distance: best.1,
+            });
+            best.0
+        } else {
+            tokens.push(Token::Literal(data[pos]));
+            1
+        };
+        for i in pos..pos + step {
+            if i + MIN_MATCH <= data.len() {
+                seen.entry(&data[i..i + MIN_MATCH]).or_default().push(i);
+            }
+        }
+        pos += step;
+    }
+    tokens
+}
+
+/// Bits are filled starting at the least significant bit of each byte
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl BitWriter {
+    /// Write the lowest `count` bits of `value`, least significant first
+    fn write(&mut self, value: u32, count: u8) {
+        for i in 0..count {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 1 << (self.bits % 8);
+            }
+            self.bits += 1;
+        }
+    }
+
+    /// Huffman codes are written starting with their most significant bit
+    fn write_code(&mut self, code: u32, count: u8) {
+        let reversed = code.reverse_bits() >> (32 - count);
+        self.write(reversed, count);
+    }
+}
+
+/// The fixed Huffman code of a literal/length symbol, `(code, bits)`
+fn fixed_code(symbol: u16) -> (u32, u8) {
+    match symbol {
+        0..=143 => (0x30 + symbol as u32, 8),
+        144..=255 => (0x190 + (symbol - 144) as u32, 9),
+        256..=279 => ((symbol - 256) as u32, 7),
+        _ => (0xc0 + (symbol - 280) as u32, 8),
+    }
+}
+
+/// The code index and the extra bits value for `value` in one of the tables
+fn lookup(table: &[(u16, u8)], value: usize) -> (usize, u32, u8) {
+    let index = table
+        .iter()
+        .rposition(|(base, _)| *base as usize <= value)
+        .unwrap();
+    let (base, extra) = table[index];
+    (index, (value - base as usize) as u32, extra)
+}
+
+/// Compress `data` into a single final block with fixed Huffman codes
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter::default();
+    writer.write(1, 1); // BFINAL
+    writer.write(1, 2); // BTYPE = fixed Huffman
+    for token in lz77(data) {
+        match token {
+            Token::Literal(byte) => {
+                let (code, bits) = fixed_code(byte as u16);
+                writer.write_code(code, bits);
+            }
+            Token::Match { length, distance } => {
+                let (index, extra_value, extra_bits) = lookup(&LENGTHS, length);
+                let (code, bits) = fixed_code(257 + index as u16);
+                writer.write_code(code, bits);
+                writer.write(extra_value, extra_bits);
+                let (index, extra_value, extra_bits) = lookup(&DISTANCES, distance);
+                writer.write_code(index as u32, 5);
+                writer.write(extra_value, extra_bits);
+            }
+        }
+    }
+    let (code, bits) = fixed_code(256);
+    writer.write_code(code, bits);
+    writer.bytes
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    bits: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, count: u8) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.bytes.get(self.bits / 8)?;
+            value |= ((*byte as u32 >> (self.bits % 8)) & 1) << i;
+            self.bits += 1;
+        }
+        Some(value)
+    }
+
+    /// Read a fixed Huffman literal/length symbol, the code is read most significant bit first
+    fn read_symbol(&mut self) -> Option<u16> {
+        let mut code = 0;
+        for bits in 1..=9 {
+            code = (code << 1) | self.read(1)?;
+            let symbol = match (bits, code) {
+                (7, 0..=0x17) => Some(256 + code as u16),
+                (8, 0x30..=0xbf) => Some(code as u16 - 0x30),
+                (8, 0xc0..=0xc7) => Some(280 + code as u16 - 0xc0),
+                (9, 0x190..=0x1ff) => Some(144 + code as u16 - 0x190),
+                _ => None,
+            };
+            if symbol.is_some() {
+                return symbol;
+            }
+        }
+        None
+    }
+}
+
+/// Inflate stored and fixed Huffman blocks, `None` for anything malformed or unsupported
+pub fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = BitReader {
+        bytes: data,
+        bits: 0,
+    };
+    let mut out: Vec<u8> = vec![];
+    loop {
+        let last = reader.read(1)? == 1;
+        match reader.read(2)? {
+            0 => {
+                // stored, starting at the next byte boundary
+                reader.bits = reader.bits.div_ceil(8) * 8;
+                let len = reader.read(16)? as usize;
+                let nlen = reader.read(16)? as usize;
+                if len != !nlen & 0xffff {
+                    return None;
+                }
+                let start = reader.bits / 8;
+                out.extend_from_slice(data.get(start..start + len)?);
+                reader.bits += len * 8;
+            }
+            1 => loop {
+                let symbol = reader.read_symbol()?;
+                match symbol {
+                    0..=255 => out.push(symbol as u8),
+                    256 => break,
+                    _ => {
+                        let (base, extra) = *LENGTHS.get(symbol as usize - 257)?;
+                        let length = base as usize + reader.read(extra)? as usize;
+                        let code = reader.read(5)?.reverse_bits() >> 27;
+                        let (base, extra) = *DISTANCES.get(code as usize)?;
+                        let distance = base as usize + reader.read(extra)? as usize;
+                        if distance > out.len() {
+                            return None;
+                        }
+                        for _ in 0..length {
+                            out.push(out[out.len() - distance]);
+                        }
+                    }
+                }
+            },
+            _ => return None,
+        }
+        if last {
+            return Some(out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn back_references() {
+        assert_eq!(
+            lz77(b"abcabcabcd"),
+            vec![
+                Token::Literal(b'a'),
+                Token::Literal(b'b'),
+                Token::Literal(b'c'),
+                Token::Match {
+                    length: 6,
+                    distance: 3
+                },
+                Token::Literal(b'd'),
+            ]
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut data = b"Cookie: sessionid=TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=\n".to_vec();
+        data.extend(b"sessionid=TmV2ZXIgcmV2ZWFsIHRoZSBXdS1UYW5nIFNlY3JldCE=".repeat(10));
+        data.extend((0..=255u8).collect::<Vec<u8>>());
+        data.extend(vec![0; 1000]);
+        let compressed = compress(&data);
+        assert!(compressed.len() < data.len() / 2);
+        assert_eq!(decompress(&compressed), Some(data));
+        assert_eq!(decompress(&compress(b"")), Some(vec![]));
+    }
+
+    /// Raw deflate output of zlib, with fixed Huffman codes and as a stored block
+    #[test]
+    fn inflate_other_deflate() {
+        assert_eq!(
+            decompress(&hex::decode("f3747655f004622747a74800").unwrap()),
+            Some(b"ICE ICE BABY".to_vec())
+        );
+        assert_eq!(
+            decompress(&hex::decode("010300fcff616263").unwrap()),
+            Some(b"abc".to_vec())
+        );
+    }
+}
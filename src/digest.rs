@@ -0,0 +1,84 @@
+//! # Streaming interface for the in-crate hash functions
+//! `new`, any number of `update`s and a `finalize`, so nobody has to pad by hand
+//! and large inputs don't have to be in memory at once.
+//!
+//! The state can be exported and imported at any point.
+//! For a Merkle–Damgård hash, the output of a finished hash is such a state as well (s4c29),
+//! that is what `State::from_output` is for.
+
+/// Chaining value, number of bytes hashed so far and the bytes waiting for a full block
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct State<H> {
+    pub h: H,
+    pub len: u64,
+    pub buffer: Vec<u8>,
+}
+
+pub trait Digest: Sized {
+    /// Bytes per compressed block
+    const BLOCK_SIZE: usize;
+    /// Bytes of the final hash
+    const OUTPUT_SIZE: usize;
+    /// The chaining value, e.g. `[u32; 5]` for SHA-1
+    type Words: Copy;
+    type Output: AsRef<[u8]> + Clone + std::fmt::Debug + Eq;
+
+    fn new() -> Self;
+    fn update(&mut self, data: &[u8]);
+    /// Pad and return the hash
+    fn finalize(self) -> Self::Output;
+    fn export_state(&self) -> State<Self::Words>;
+    fn import_state(state: State<Self::Words>) -> Self;
+    /// The padding appended to a message of `len` bytes
+    fn padding(len: usize) -> Vec<u8>;
+    /// The chaining value that produced `output`, `None` if the output is truncated (SHA-224, SHA-384)
+    fn words_from_output(output: &Self::Output) -> Option<Self::Words>;
+
+    /// Hash everything at once
+    fn digest(data: &[u8]) -> Self::Output {
+        let mut digest = Self::new();
+        digest.update(data);
+        digest.finalize()
+    }
+}
+
+impl<H> State<H> {
+    /// Continue where a hash over `len` bytes (including the padding) stopped with `output`
+    pub fn from_output<D: Digest<Words = H>>(output: &D::Output, len: u64) -> Option<Self> {
+        assert!(
+            len.is_multiple_of(D::BLOCK_SIZE as u64),
+            "a finished hash ends on a block boundary"
+        );
+        Some(State {
+            h: D::words_from_output(output)?,
+            len,
+            buffer: vec![],
+        })
+    }
+}
+
+/// Buffer `data` and feed every complete block to `compress`
+pub(crate) fn update_blocks<H>(
+    state: &mut State<H>,
+    data: &[u8],
+    block_size: usize,
+    mut compress: impl FnMut(&mut H, &[u8]),
+) {
+    state.len += data.len() as u64;
+    let mut data = data;
+    if !state.buffer.is_empty() {
+        let missing = (block_size - state.buffer.len()).min(data.len());
+        state.buffer.extend_from_slice(&data[..missing]);
+        data = &data[missing..];
+        if state.buffer.len() < block_size {
+            return;
+        }
+        compress(&mut state.h, &state.buffer);
+        state.buffer.clear();
+    }
+    let mut blocks = data.chunks_exact(block_size);
+    for block in &mut blocks {
+        compress(&mut state.h, block);
+    }
+    state.buffer.extend_from_slice(blocks.remainder());
+}
@@ -0,0 +1,295 @@
+//! # AES-GCM (NIST SP 800-38D)
+//! CTR mode encryption plus GHASH, a polynomial MAC over GF(2^128) evaluated at `H = E_k(0^128)`.
+//!
+//! A block is interpreted as a field element in GCM's reflected bit order:
+//! the most significant bit of the first byte is the coefficient of `x^0`.
+//! Storing blocks as big endian `u128` means `x^0` is the top bit and multiplying by `x` is a right shift.
+
+use crate::blockcipher::{aes, BlockCipher};
+use crate::{constant_time_eq, CryptoError};
+
+/// `x^128 = x^7 + x^2 + x + 1`, in the reflected representation
+const R: u128 = 0xe1 << 120;
+
+/// SP 800-38D allows 4 byte tags at least, the tests go below that to keep the
+/// attack in `attacks::gcm_truncated_tag` fast
+const MIN_TAG_LEN: usize = if cfg!(test) { 1 } else { 4 };
+
+/// Multiplication in GF(2^128) as defined for GCM
+pub fn gf_mul(x: u128, y: u128) -> u128 {
+    let mut z = 0;
+    let mut v = y;
+    for i in 0..128 {
+        if (x >> (127 - i)) & 1 == 1 {
+            z ^= v;
+        }
+        v = if v & 1 == 1 { (v >> 1) ^ R } else { v >> 1 };
+    }
+    z
+}
+
+/// `x^e` in GF(2^128) by square and multiply
+pub fn gf_pow(x: u128, mut e: u128) -> u128 {
+    let mut result = 1 << 127;
+    let mut base = x;
+    while e != 0 {
+        if e & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        e >>= 1;
+    }
+    result
+}
+
+/// Multiplicative inverse, `x^(2^128 - 2)`
+pub fn gf_inv(x: u128) -> u128 {
+    assert_ne!(x, 0, "zero has no inverse");
+    gf_pow(x, u128::MAX - 1)
+}
+
+/// Interpret up to 16 bytes as a block, zero padded on the right
+pub fn to_block(bytes: &[u8]) -> u128 {
+    assert!(bytes.len() <= 16);
+    let mut block = [0; 16];
+    block[..bytes.len()].copy_from_slice(bytes);
+    u128::from_be_bytes(block)
+}
+
+/// The blocks GHASH is computed over: padded aad, padded ciphertext and the length block
+pub fn ghash_blocks(aad: &[u8], cipher: &[u8]) -> Vec<u128> {
+    let mut blocks: Vec<u128> = aad.chunks(16).map(to_block).collect();
+    blocks.extend(cipher.chunks(16).map(to_block));
+    blocks.push(((aad.len() as u128 * 8) << 64) | (cipher.len() as u128 * 8));
+    blocks
+}
+
+/// `GHASH_H(blocks) = b_1 * H^m + b_2 * H^(m-1) + ... + b_m * H`
+pub fn ghash(h: u128, aad: &[u8], cipher: &[u8]) -> u128 {
+    ghash_blocks(aad, cipher)
+        .iter()
+        .fold(0, |acc, block| gf_mul(acc ^ block, h))
+}
+
+/// Increment the rightmost 32 bits
+fn inc32(block: u128) -> u128 {
+    let counter = (block as u32).wrapping_add(1);
+    (block & !(u32::MAX as u128)) | counter as u128
+}
+
+struct Gcm {
+    block_cipher: Box<dyn BlockCipher>,
+    h: u128,
+    j0: u128,
+}
+
+impl Gcm {
+    fn new(key: &[u8], nonce: &[u8]) -> Self {
+        assert!(!nonce.is_empty(), "nonce must not be empty");
+        let block_cipher = aes(key);
+        let h = u128::from_be_bytes(to_array(&block_cipher.encrypt_block(&[0; 16])));
+        let j0 = if nonce.len() == 12 {
+            to_block(nonce) | 1
+        } else {
+            let mut blocks: Vec<u128> = nonce.chunks(16).map(to_block).collect();
+            blocks.push(nonce.len() as u128 * 8);
+            blocks.iter().fold(0, |acc, block| gf_mul(acc ^ block, h))
+        };
+        Gcm {
+            block_cipher,
+            h,
+            j0,
+        }
+    }
+
+    fn encrypt_counter(&self, counter: u128) -> Vec<u8> {
+        self.block_cipher.encrypt_block(&counter.to_be_bytes())
+    }
+
+    /// CTR mode starting with `inc32(J0)`, the same for both directions
+    fn ctr(&self, data: &[u8]) -> Vec<u8> {
+        let mut counter = self.j0;
+        let mut result = Vec::with_capacity(data.len());
+        for chunk in data.chunks(16) {
+            counter = inc32(counter);
+            let keystream = self.encrypt_counter(counter);
+            result.extend(chunk.iter().zip(keystream).map(|(x, y)| x ^ y));
+        }
+        result
+    }
+
+    fn tag(&self, aad: &[u8], cipher: &[u8], tag_len: usize) -> Vec<u8> {
+        let mask = u128::from_be_bytes(to_array(&self.encrypt_counter(self.j0)));
+        (ghash(self.h, aad, cipher) ^ mask).to_be_bytes()[..tag_len].to_vec()
+    }
+}
+
+fn to_array(block: &[u8]) -> [u8; 16] {
+    let mut array = [0; 16];
+    array.copy_from_slice(block);
+    array
+}
+
+/// Encrypt and authenticate, returns `(ciphertext, tag)`.
+/// The tag is truncated to `tag_len` bytes (4 to 16, anything below 12 only for special uses).
+pub fn gcm_encrypt(
+    key: &[u8],
+    nonce: &[u8],
+    plain: &[u8],
+    aad: &[u8],
+    tag_len: usize,
+) -> (Vec<u8>, Vec<u8>) {
+    assert!((MIN_TAG_LEN..=16).contains(&tag_len), "invalid tag length");
+    let gcm = Gcm::new(key, nonce);
+    let cipher = gcm.ctr(plain);
+    let tag = gcm.tag(aad, &cipher, tag_len);
+    (cipher, tag)
+}
+
+/// The full 16 byte tag of a ciphertext, truncate it as needed
+pub fn gcm_tag(key: &[u8], nonce: &[u8], cipher: &[u8], aad: &[u8]) -> Vec<u8> {
+    Gcm::new(key, nonce).tag(aad, cipher, 16)
+}
+
+/// Verify the tag and only then decrypt.
+/// `tag_len` is fixed for the key like in `gcm_encrypt`, a tag of any other length is rejected,
+/// otherwise a forger could simply cut a valid tag down to 4 bytes.
+pub fn gcm_decrypt(
+    key: &[u8],
+    nonce: &[u8],
+    cipher: &[u8],
+    aad: &[u8],
+    tag: &[u8],
+    tag_len: usize,
+) -> Result<Vec<u8>, CryptoError> {
+    assert!((MIN_TAG_LEN..=16).contains(&tag_len), "invalid tag length");
+    if tag.len() != tag_len {
+        return Err(CryptoError::Authentication);
+    }
+    let gcm = Gcm::new(key, nonce);
+    let expected = gcm.tag(aad, cipher, tag_len);
+    if !constant_time_eq(&expected, tag) {
+        return Err(CryptoError::Authentication);
+    }
+    Ok(gcm.ctr(cipher))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    const KEY: &str = "feffe9928665731c6d6a8f9467308308";
+    const PLAIN: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                         1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39";
+    const AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+
+    #[test]
+    fn field_arithmetic() {
+        let one = 1 << 127;
+        let x = to_block(b"some field elem");
+        let y = to_block(b"another one");
+        assert_eq!(gf_mul(x, one), x);
+        assert_eq!(gf_mul(x, y), gf_mul(y, x));
+        assert_eq!(gf_mul(x, gf_inv(x)), one);
+        assert_eq!(gf_pow(x, 3), gf_mul(x, gf_mul(x, x)));
+    }
+
+    /// Test cases 1 and 2 of the GCM specification
+    #[test]
+    fn zero_key() {
+        let (cipher, tag) = gcm_encrypt(&[0; 16], &[0; 12], &[], &[], 16);
+        assert!(cipher.is_empty());
+        assert_eq!(tag, h("58e2fccefa7e3061367f1d57a4e7455a"));
+
+        let (cipher, tag) = gcm_encrypt(&[0; 16], &[0; 12], &[0; 16], &[], 16);
+        assert_eq!(cipher, h("0388dace60b6a392f328c2b971b2fe78"));
+        assert_eq!(tag, h("ab6e47d42cec13bdf53a67b21257bddf"));
+    }
+
+    /// Test cases 4 to 6 (96 bit, 64 bit and 480 bit nonces) and 16 (AES-256)
+    #[test]
+    fn nist_vectors() {
+        let cases = [
+            (
+                KEY,
+                "cafebabefacedbaddecaf888",
+                "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+                 21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+                "5bc94fbc3221a5db94fae95ae7121a47",
+            ),
+            (
+                KEY,
+                "cafebabefacedbad",
+                "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+                 73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+                "3612d2e79e3b0785561be14aaca2fccb",
+            ),
+            (
+                KEY,
+                "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+                 c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b",
+                "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+                 01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+                "619cc5aefffe0bfa462af43c1699d050",
+            ),
+            (
+                "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+                "cafebabefacedbaddecaf888",
+                "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+                 8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+                "76fc6ece0f4e1768cddf8853bb2d551b",
+            ),
+        ];
+        for (key, nonce, cipher, tag) in cases.iter() {
+            let (key, nonce, plain, aad) = (h(key), h(nonce), h(PLAIN), h(AAD));
+            let (c, t) = gcm_encrypt(&key, &nonce, &plain, &aad, 16);
+            assert_eq!(hex::encode(&c), *cipher);
+            assert_eq!(hex::encode(&t), *tag);
+            assert_eq!(gcm_decrypt(&key, &nonce, &c, &aad, &t, 16), Ok(plain));
+        }
+    }
+
+    #[test]
+    fn truncated_tag() {
+        let (key, nonce, plain, aad) = (h(KEY), h("cafebabefacedbaddecaf888"), h(PLAIN), h(AAD));
+        let (cipher, tag) = gcm_encrypt(&key, &nonce, &plain, &aad, 12);
+        assert_eq!(tag, h("5bc94fbc3221a5db94fae95a"));
+        assert_eq!(
+            gcm_decrypt(&key, &nonce, &cipher, &aad, &tag, 12),
+            Ok(plain)
+        );
+        assert_eq!(
+            gcm_decrypt(&key, &nonce, &cipher, &aad, &tag[..4], 12),
+            Err(CryptoError::Authentication)
+        );
+        let full_tag = gcm_tag(&key, &nonce, &cipher, &aad);
+        assert_eq!(full_tag[..12], tag[..]);
+        assert_eq!(
+            gcm_decrypt(&key, &nonce, &cipher, &aad, &full_tag, 12),
+            Err(CryptoError::Authentication)
+        );
+    }
+
+    #[test]
+    fn rejects_forgeries() {
+        let (key, nonce, plain, aad) = (h(KEY), h("cafebabefacedbaddecaf888"), h(PLAIN), h(AAD));
+        let (mut cipher, tag) = gcm_encrypt(&key, &nonce, &plain, &aad, 16);
+        assert_eq!(
+            gcm_decrypt(&key, &nonce, &cipher, b"other aad", &tag, 16),
+            Err(CryptoError::Authentication)
+        );
+        assert_eq!(
+            gcm_decrypt(&key, &nonce, &cipher, &aad, &tag[..3], 16),
+            Err(CryptoError::Authentication)
+        );
+        cipher[3] ^= 1;
+        assert_eq!(
+            gcm_decrypt(&key, &nonce, &cipher, &aad, &tag, 16),
+            Err(CryptoError::Authentication)
+        );
+    }
+}
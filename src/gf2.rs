@@ -0,0 +1,194 @@
+//! # Linear algebra over GF(2)
+//! Just enough for attacks that turn a cipher into a system of linear equations.
+//! Rows are packed into `u64` words, adding two rows is a word-wise xor.
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BitMatrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<Vec<u64>>,
+}
+
+fn words(cols: usize) -> usize {
+    cols.div_ceil(64)
+}
+
+impl BitMatrix {
+    pub fn zero(rows: usize, cols: usize) -> Self {
+        BitMatrix {
+            rows,
+            cols,
+            data: vec![vec![0; words(cols)]; rows],
+        }
+    }
+
+    pub fn identity(n: usize) -> Self {
+        let mut m = BitMatrix::zero(n, n);
+        for i in 0..n {
+            m.set(i, i, true);
+        }
+        m
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> bool {
+        assert!(row < self.rows && col < self.cols, "index out of bounds");
+        (self.data[row][col / 64] >> (col % 64)) & 1 == 1
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: bool) {
+        assert!(row < self.rows && col < self.cols, "index out of bounds");
+        let mask = 1 << (col % 64);
+        if value {
+            self.data[row][col / 64] |= mask;
+        } else {
+            self.data[row][col / 64] &= !mask;
+        }
+    }
+
+    /// Append all rows of `other`, which needs the same number of columns
+    pub fn append_rows(&mut self, other: &BitMatrix) {
+        assert_eq!(self.cols, other.cols, "column count differs");
+        self.data.extend(other.data.iter().cloned());
+        self.rows += other.rows;
+    }
+
+    /// A new matrix consisting of the rows in `range`
+    pub fn select_rows(&self, range: std::ops::Range<usize>) -> BitMatrix {
+        assert!(range.end <= self.rows, "index out of bounds");
+        BitMatrix {
+            rows: range.len(),
+            cols: self.cols,
+            data: self.data[range].to_vec(),
+        }
+    }
+
+    fn xor_row_into(&mut self, from: usize, to: usize) {
+        let (source, target) = if from < to {
+            let (head, tail) = self.data.split_at_mut(to);
+            (&head[from], &mut tail[0])
+        } else {
+            let (head, tail) = self.data.split_at_mut(from);
+            (&tail[0], &mut head[to])
+        };
+        for (t, s) in target.iter_mut().zip(source.iter()) {
+            *t ^= s;
+        }
+    }
+
+    pub fn transpose(&self) -> BitMatrix {
+        let mut t = BitMatrix::zero(self.cols, self.rows);
+        for r in 0..self.rows {
+            for c in 0..self.cols {
+                if self.get(r, c) {
+                    t.set(c, r, true);
+                }
+            }
+        }
+        t
+    }
+
+    pub fn mul(&self, other: &BitMatrix) -> BitMatrix {
+        assert_eq!(self.cols, other.rows, "dimensions do not match");
+        let mut product = BitMatrix::zero(self.rows, other.cols);
+        for r in 0..self.rows {
+            for k in 0..self.cols {
+                if self.get(r, k) {
+                    for (p, o) in product.data[r].iter_mut().zip(other.data[k].iter()) {
+                        *p ^= o;
+                    }
+                }
+            }
+        }
+        product
+    }
+
+    /// Bring the matrix into reduced row echelon form, returns the pivot columns
+    pub fn reduce(&mut self) -> Vec<usize> {
+        let mut pivots = Vec::new();
+        let mut row = 0;
+        for col in 0..self.cols {
+            if row == self.rows {
+                break;
+            }
+            let pivot = match (row..self.rows).find(|&r| self.get(r, col)) {
+                Some(pivot) => pivot,
+                None => continue,
+            };
+            self.data.swap(row, pivot);
+            for r in 0..self.rows {
+                if r != row && self.get(r, col) {
+                    self.xor_row_into(row, r);
+                }
+            }
+            pivots.push(col);
+            row += 1;
+        }
+        pivots
+    }
+
+    pub fn rank(&self) -> usize {
+        self.clone().reduce().len()
+    }
+
+    /// A basis of `{ v | self * v = 0 }`, one vector per row of the result
+    pub fn kernel(&self) -> BitMatrix {
+        let mut reduced = self.clone();
+        let pivots = reduced.reduce();
+        let free: Vec<usize> = (0..self.cols).filter(|c| !pivots.contains(c)).collect();
+        let mut basis = BitMatrix::zero(free.len(), self.cols);
+        for (i, &f) in free.iter().enumerate() {
+            basis.set(i, f, true);
+            for (row, &p) in pivots.iter().enumerate() {
+                if reduced.get(row, f) {
+                    basis.set(i, p, true);
+                }
+            }
+        }
+        basis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_matrix(rows: usize, cols: usize) -> BitMatrix {
+        let mut m = BitMatrix::zero(rows, cols);
+        for r in 0..rows {
+            for c in 0..cols {
+                m.set(r, c, rand::random());
+            }
+        }
+        m
+    }
+
+    #[test]
+    fn multiplication() {
+        let m = random_matrix(70, 130);
+        assert_eq!(BitMatrix::identity(70).mul(&m), m);
+        assert_eq!(m.mul(&BitMatrix::identity(130)), m);
+        let n = random_matrix(130, 5);
+        assert_eq!(m.mul(&n).transpose(), n.transpose().mul(&m.transpose()));
+    }
+
+    #[test]
+    fn kernel() {
+        let m = random_matrix(100, 150);
+        let kernel = m.kernel();
+        assert_eq!(kernel.rows() + m.rank(), 150);
+        assert_eq!(kernel.rank(), kernel.rows(), "basis is independent");
+        assert_eq!(
+            m.mul(&kernel.transpose()),
+            BitMatrix::zero(100, kernel.rows())
+        );
+        assert_eq!(BitMatrix::identity(10).kernel().rows(), 0);
+    }
+}
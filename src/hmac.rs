@@ -0,0 +1,96 @@
+//! # HMAC (RFC 2104) over any `Digest`
+//! `H((key ^ opad) || H((key ^ ipad) || message))`, the outer hash hides the inner state,
+//! so unlike `H(key || message)` there is nothing to extend (s4c29).
+//! Keys longer than a block are hashed first, shorter ones are padded with zeros.
+
+use crate::constant_time_eq;
+use crate::digest::Digest;
+
+const IPAD: u8 = 0x36;
+const OPAD: u8 = 0x5c;
+
+pub fn hmac<D: Digest>(key: &[u8], message: &[u8]) -> D::Output {
+    let mut block = if key.len() > D::BLOCK_SIZE {
+        D::digest(key).as_ref().to_vec()
+    } else {
+        key.to_vec()
+    };
+    block.resize(D::BLOCK_SIZE, 0);
+
+    let mut inner = D::new();
+    inner.update(&block.iter().map(|b| b ^ IPAD).collect::<Vec<u8>>());
+    inner.update(message);
+    let inner = inner.finalize();
+
+    let mut outer = D::new();
+    outer.update(&block.iter().map(|b| b ^ OPAD).collect::<Vec<u8>>());
+    outer.update(inner.as_ref());
+    outer.finalize()
+}
+
+/// Recompute the MAC and compare it in constant time
+pub fn verify<D: Digest>(key: &[u8], message: &[u8], mac: &[u8]) -> bool {
+    constant_time_eq(hmac::<D>(key, message).as_ref(), mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::md5::MyMd5;
+    use crate::sha1::MySha1;
+    use crate::sha2::{MySha224, MySha256, MySha384, MySha512};
+
+    /// Test case 2 of RFC 2202 and RFC 4231
+    #[test]
+    fn what_do_ya_want_for_nothing() {
+        let (key, message) = (b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            hex::encode(hmac::<MyMd5>(key, message)),
+            "750c783e6ab0b503eaa86e310a5db738"
+        );
+        assert_eq!(
+            hex::encode(hmac::<MySha1>(key, message)),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
+        );
+        assert_eq!(
+            hex::encode(hmac::<MySha224>(key, message)),
+            "a30e01098bc6dbbf45690f3a7e9e6d0f8bbea2a39e6148008fd05e44"
+        );
+        assert_eq!(
+            hex::encode(hmac::<MySha256>(key, message)),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            hex::encode(hmac::<MySha384>(key, message)),
+            "af45d2e376484031617f78d2b58a6b1b9c7ef464f5a01b47e42ec3736322445e8e2240ca5e69e2c78b3239ecfab21649"
+        );
+        assert_eq!(
+            hex::encode(hmac::<MySha512>(key, message)),
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+    }
+
+    /// Test cases 1 and 6 of RFC 4231
+    #[test]
+    fn short_and_long_keys() {
+        assert_eq!(
+            hex::encode(hmac::<MySha256>(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex::encode(hmac::<MySha256>(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn verification() {
+        let mac = hmac::<MySha256>(b"key", b"message");
+        assert!(verify::<MySha256>(b"key", b"message", &mac));
+        assert!(!verify::<MySha256>(b"key", b"messagE", &mac));
+        assert!(!verify::<MySha256>(b"key", b"message", &mac[..31]));
+    }
+}
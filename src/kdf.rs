@@ -0,0 +1,135 @@
+//! # Key derivation with HMAC
+//! - HKDF (RFC 5869) for secrets that are already random, like a DH shared secret:
+//!   `extract` concentrates the entropy into a pseudorandom key, `expand` stretches it to any length,
+//!   with `info` separating keys for different purposes
+//! - PBKDF2 (RFC 8018) for passwords: thousands of HMAC iterations make every guess expensive
+
+use crate::digest::Digest;
+use crate::hmac::hmac;
+
+/// `PRK = HMAC(salt, ikm)`, an empty salt stands for `OUTPUT_SIZE` zero bytes
+pub fn hkdf_extract<D: Digest>(salt: &[u8], ikm: &[u8]) -> D::Output {
+    if salt.is_empty() {
+        hmac::<D>(&vec![0; D::OUTPUT_SIZE], ikm)
+    } else {
+        hmac::<D>(salt, ikm)
+    }
+}
+
+/// `T(i) = HMAC(prk, T(i - 1) || info || i)` until `len` bytes are there, at most 255 blocks
+pub fn hkdf_expand<D: Digest>(prk: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    assert!(
+        len <= 255 * D::OUTPUT_SIZE,
+        "HKDF can't expand to more than 255 blocks"
+    );
+    let mut okm = vec![];
+    let mut block = vec![];
+    for i in 1..=len.div_ceil(D::OUTPUT_SIZE) {
+        block = hmac::<D>(prk, &[&block, info, &[i as u8]].concat())
+            .as_ref()
+            .to_vec();
+        okm.extend_from_slice(&block);
+    }
+    okm.truncate(len);
+    okm
+}
+
+pub fn hkdf<D: Digest>(salt: &[u8], ikm: &[u8], info: &[u8], len: usize) -> Vec<u8> {
+    hkdf_expand::<D>(hkdf_extract::<D>(salt, ikm).as_ref(), info, len)
+}
+
+/// `T(i) = U(1) ^ .. ^ U(c)` with `U(1) = HMAC(password, salt || i)` and `U(j) = HMAC(password, U(j - 1))`
+pub fn pbkdf2<D: Digest>(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    assert!(iterations > 0, "at least one iteration");
+    let mut dk = vec![];
+    for i in 1..=len.div_ceil(D::OUTPUT_SIZE) as u32 {
+        let mut u = hmac::<D>(password, &[salt, &i.to_be_bytes()].concat());
+        let mut t = u.as_ref().to_vec();
+        for _ in 1..iterations {
+            u = hmac::<D>(password, u.as_ref());
+            t.iter_mut().zip(u.as_ref()).for_each(|(t, u)| *t ^= u);
+        }
+        dk.extend(t);
+    }
+    dk.truncate(len);
+    dk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sha1::MySha1;
+    use crate::sha2::MySha256;
+
+    /// Test cases 1, 3 and 4 of RFC 5869
+    #[test]
+    fn rfc_5869() {
+        let salt: Vec<u8> = (0..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        let prk = hkdf_extract::<MySha256>(&salt, &[0x0b; 22]);
+        assert_eq!(
+            hex::encode(prk),
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5"
+        );
+        assert_eq!(
+            hex::encode(hkdf_expand::<MySha256>(&prk, &info, 42)),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        );
+        assert_eq!(
+            hex::encode(hkdf::<MySha256>(b"", &[0x0b; 22], b"", 42)),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"
+        );
+        assert_eq!(
+            hex::encode(hkdf::<MySha1>(&salt, &[0x0b; 11], &info, 42)),
+            "085a01ea1b10f36933068b56efa5ad81a4f14b822f5b091568a9cdd4f155fda2c22e422478d305f3f896"
+        );
+    }
+
+    /// PBKDF2-HMAC-SHA1 vectors of RFC 6070, except for the one with 16777216 iterations
+    #[test]
+    fn rfc_6070() {
+        let vectors: [(&[u8], &[u8], u32, &str); 5] = [
+            (
+                b"password",
+                b"salt",
+                1,
+                "0c60c80f961f0e71f3a9b524af6012062fe037a6",
+            ),
+            (
+                b"password",
+                b"salt",
+                2,
+                "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957",
+            ),
+            (
+                b"password",
+                b"salt",
+                4096,
+                "4b007901b765489abead49d926f721d065a429c1",
+            ),
+            (
+                b"passwordPASSWORDpassword",
+                b"saltSALTsaltSALTsaltSALTsaltSALTsalt",
+                4096,
+                "3d2eec4fe41c849b80c8d83662c0e44a8b291a964cf2f07038",
+            ),
+            (
+                b"pass\0word",
+                b"sa\0lt",
+                4096,
+                "56fa6aa75548099dcc37d7f03425e0c3",
+            ),
+        ];
+        for (password, salt, iterations, expected) in vectors.iter() {
+            let len = expected.len() / 2;
+            assert_eq!(
+                hex::encode(pbkdf2::<MySha1>(password, salt, *iterations, len)),
+                *expected
+            );
+        }
+        assert_eq!(
+            hex::encode(pbkdf2::<MySha256>(b"password", b"salt", 4096, 32)),
+            "c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+        );
+    }
+}
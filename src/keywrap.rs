@@ -0,0 +1,247 @@
+//! # AES key wrap (RFC 3394) and key wrap with padding (RFC 5649)
+//! Encrypts key material under a key encryption key (KEK), e.g. a key derived from a DH exchange like in s5c34.
+//! No nonce is needed, the wrap is deterministic and works on 64 bit halves of AES blocks:
+//! - `A` starts as an integrity check value, `R_1..R_n` are the key's 64 bit blocks
+//! - 6 rounds over all `R_i`: `A || R_i = AES(A || R_i)` and `A ^= t` with a counter `t`
+//! - unwrapping runs this backwards, if `A` does not come out as the check value, something changed
+//!
+//! RFC 5649 puts the key length into the check value, so the key can be zero padded to a multiple of 8 bytes.
+
+use crate::blockcipher::{aes, BlockCipher};
+use crate::CryptoError;
+
+/// Default initial value of RFC 3394
+const IV: u64 = 0xa6a6a6a6a6a6a6a6;
+/// The high half of the RFC 5649 check value, the low half is the key length
+const AIV_PREFIX: u32 = 0xa65959a6;
+
+fn encrypt(block_cipher: &dyn BlockCipher, a: u64, r: u64) -> (u64, u64) {
+    let mut block = a.to_be_bytes().to_vec();
+    block.extend_from_slice(&r.to_be_bytes());
+    let out = u128::from_be_bytes(to_array(&block_cipher.encrypt_block(&block)));
+    ((out >> 64) as u64, out as u64)
+}
+
+fn decrypt(block_cipher: &dyn BlockCipher, a: u64, r: u64) -> (u64, u64) {
+    let mut block = a.to_be_bytes().to_vec();
+    block.extend_from_slice(&r.to_be_bytes());
+    let out = u128::from_be_bytes(to_array(&block_cipher.decrypt_block(&block)));
+    ((out >> 64) as u64, out as u64)
+}
+
+fn to_array(block: &[u8]) -> [u8; 16] {
+    let mut array = [0; 16];
+    array.copy_from_slice(block);
+    array
+}
+
+fn to_halves(data: &[u8]) -> Vec<u64> {
+    data.chunks(8)
+        .map(|chunk| {
+            let mut half = [0; 8];
+            half.copy_from_slice(chunk);
+            u64::from_be_bytes(half)
+        })
+        .collect()
+}
+
+fn from_halves(a: u64, r: &[u64]) -> Vec<u8> {
+    let mut bytes = a.to_be_bytes().to_vec();
+    for r_i in r {
+        bytes.extend_from_slice(&r_i.to_be_bytes());
+    }
+    bytes
+}
+
+/// The wrapping function `W` with the check value `a`, at least 2 halves
+fn wrap_with(block_cipher: &dyn BlockCipher, mut a: u64, plain: &[u8]) -> Vec<u8> {
+    let mut r = to_halves(plain);
+    let n = r.len() as u64;
+    for j in 0..6 {
+        for (i, r_i) in r.iter_mut().enumerate() {
+            let (b_high, b_low) = encrypt(block_cipher, a, *r_i);
+            a = b_high ^ (n * j + i as u64 + 1);
+            *r_i = b_low;
+        }
+    }
+    from_halves(a, &r)
+}
+
+/// The unwrapping function `W^-1`, returns the check value and the halves of the key
+fn unwrap_with(block_cipher: &dyn BlockCipher, wrapped: &[u8]) -> (u64, Vec<u64>) {
+    let mut halves = to_halves(wrapped);
+    let mut a = halves.remove(0);
+    let mut r = halves;
+    let n = r.len() as u64;
+    for j in (0..6).rev() {
+        for (i, r_i) in r.iter_mut().enumerate().rev() {
+            let (b_high, b_low) = decrypt(block_cipher, a ^ (n * j + i as u64 + 1), *r_i);
+            a = b_high;
+            *r_i = b_low;
+        }
+    }
+    (a, r)
+}
+
+/// Wrap a key of at least 16 bytes and a multiple of 8 bytes with the 16, 24 or 32 byte `kek`
+pub fn key_wrap(kek: &[u8], key: &[u8]) -> Vec<u8> {
+    assert!(
+        key.len() >= 16 && key.len().is_multiple_of(8),
+        "key has to be at least 2 halves of 64 bits"
+    );
+    wrap_with(aes(kek).as_ref(), IV, key)
+}
+
+/// Unwrap and check the integrity of a key wrapped with `key_wrap`
+pub fn key_unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if wrapped.len() < 24 || !wrapped.len().is_multiple_of(8) {
+        return Err(CryptoError::KeyWrapIntegrity);
+    }
+    let (a, r) = unwrap_with(aes(kek).as_ref(), wrapped);
+    if a != IV {
+        return Err(CryptoError::KeyWrapIntegrity);
+    }
+    Ok(from_halves(a, &r)[8..].to_vec())
+}
+
+/// Wrap a key of any length between 1 and 2^32 - 1 bytes
+pub fn key_wrap_padded(kek: &[u8], key: &[u8]) -> Vec<u8> {
+    assert!(
+        !key.is_empty() && key.len() <= u32::MAX as usize,
+        "key length has to fit into 32 bits"
+    );
+    let aiv = ((AIV_PREFIX as u64) << 32) | key.len() as u64;
+    let mut padded = key.to_vec();
+    padded.resize(key.len().div_ceil(8) * 8, 0);
+    let block_cipher = aes(kek);
+    if padded.len() == 8 {
+        // a single half is encrypted directly as one AES block
+        let (a, r) = encrypt(block_cipher.as_ref(), aiv, to_halves(&padded)[0]);
+        from_halves(a, &[r])
+    } else {
+        wrap_with(block_cipher.as_ref(), aiv, &padded)
+    }
+}
+
+/// Unwrap a key wrapped with `key_wrap_padded`, checking the prefix, the length and the zero padding
+pub fn key_unwrap_padded(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if wrapped.len() < 16 || !wrapped.len().is_multiple_of(8) {
+        return Err(CryptoError::KeyWrapIntegrity);
+    }
+    let block_cipher = aes(kek);
+    let (a, r) = if wrapped.len() == 16 {
+        let halves = to_halves(wrapped);
+        let (a, r) = decrypt(block_cipher.as_ref(), halves[0], halves[1]);
+        (a, vec![r])
+    } else {
+        unwrap_with(block_cipher.as_ref(), wrapped)
+    };
+    let padded = from_halves(a, &r)[8..].to_vec();
+    let len = (a as u32) as usize;
+    let prefix_ok = (a >> 32) as u32 == AIV_PREFIX;
+    let len_ok = len > padded.len() - 8 && len <= padded.len();
+    if !prefix_ok || !len_ok || padded[len..].iter().any(|b| *b != 0) {
+        return Err(CryptoError::KeyWrapIntegrity);
+    }
+    Ok(padded[..len].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    /// RFC 3394 section 4, all KEK and key size combinations
+    #[test]
+    fn rfc_3394() {
+        let vectors = [
+            (
+                "000102030405060708090a0b0c0d0e0f",
+                "00112233445566778899aabbccddeeff",
+                "1fa68b0a8112b447aef34bd8fb5a7b829d3e862371d2cfe5",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f1011121314151617",
+                "00112233445566778899aabbccddeeff",
+                "96778b25ae6ca435f92b5b97c050aed2468ab8a17ad84e5d",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "00112233445566778899aabbccddeeff",
+                "64e8c3f9ce0f5ba263e9777905818a2a93c8191e7d6e8ae7",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f1011121314151617",
+                "00112233445566778899aabbccddeeff0001020304050607",
+                "031d33264e15d33268f24ec260743edce1c6c7ddee725a936ba814915c6762d2",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "00112233445566778899aabbccddeeff0001020304050607",
+                "a8f9bc1612c68b3ff6e6f4fbe30e71e4769c8b80a32cb8958cd5d17d6b254da1",
+            ),
+            (
+                "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f",
+                "00112233445566778899aabbccddeeff000102030405060708090a0b0c0d0e0f",
+                "28c9f404c4b810f4cbccb35cfb87f8263f5786e2d80ed326cbc7f0e71a99f43bfb988b9b7a02dd21",
+            ),
+        ];
+        for (kek, key, wrapped) in vectors.iter() {
+            assert_eq!(key_wrap(&h(kek), &h(key)), h(wrapped));
+            assert_eq!(key_unwrap(&h(kek), &h(wrapped)), Ok(h(key)));
+        }
+    }
+
+    /// RFC 5649 section 6
+    #[test]
+    fn rfc_5649() {
+        let kek = h("5840df6e29b02af1ab493b705bf16ea1ae8338f4dcc176a8");
+        let vectors = [
+            (
+                "c37b7e6492584340bed12207808941155068f738",
+                "138bdeaa9b8fa7fc61f97742e72248ee5ae6ae5360d1ae6a5f54f373fa543b6a",
+            ),
+            ("466f7250617369", "afbeb0f07dfbf5419200f2ccb50bb24f"),
+        ];
+        for (key, wrapped) in vectors.iter() {
+            assert_eq!(key_wrap_padded(&kek, &h(key)), h(wrapped));
+            assert_eq!(key_unwrap_padded(&kek, &h(wrapped)), Ok(h(key)));
+        }
+    }
+
+    #[test]
+    fn integrity_failure() {
+        let kek = [7; 16];
+        let mut wrapped = key_wrap(&kek, &[42; 32]);
+        wrapped[20] ^= 1;
+        assert_eq!(
+            key_unwrap(&kek, &wrapped),
+            Err(CryptoError::KeyWrapIntegrity)
+        );
+        assert_eq!(
+            key_unwrap(&[8; 16], &key_wrap(&kek, &[42; 32])),
+            Err(CryptoError::KeyWrapIntegrity)
+        );
+        assert_eq!(
+            key_unwrap(&kek, &wrapped[..20]),
+            Err(CryptoError::KeyWrapIntegrity)
+        );
+
+        // both wrap variants use a different check value, one can't be unwrapped as the other
+        let wrapped = key_wrap_padded(&kek, &[42; 16]);
+        assert_eq!(key_unwrap_padded(&kek, &wrapped), Ok(vec![42; 16]));
+        assert_eq!(
+            key_unwrap(&kek, &wrapped),
+            Err(CryptoError::KeyWrapIntegrity)
+        );
+        let mut wrapped = key_wrap_padded(&kek, &[42; 5]);
+        wrapped[0] ^= 1;
+        assert_eq!(
+            key_unwrap_padded(&kek, &wrapped),
+            Err(CryptoError::KeyWrapIntegrity)
+        );
+    }
+}
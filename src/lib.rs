@@ -5,18 +5,37 @@ extern crate log;
 
 use std::collections::HashSet;
 
-use crypto::aessafe;
-use crypto::symmetriccipher::{BlockDecryptor, BlockEncryptor};
 use rand::distributions::Standard;
 use rand::{thread_rng, Rng};
 
+use crate::blockcipher::{aes, BlockCipher};
+use crate::padding::Padding;
+
+pub mod aes;
+pub mod attacks;
+pub mod blockcipher;
+pub mod cbc_mac;
+pub mod chacha20;
+pub mod channel;
+pub mod deflate;
 pub mod dh;
+pub mod digest;
+pub mod gcm;
+pub mod gf2;
+pub mod hmac;
+pub mod kdf;
+pub mod keywrap;
 pub mod md4;
+pub mod md5;
 pub mod mt19937;
+pub mod padding;
+pub mod poly1305;
 pub mod primes;
 pub mod rsa;
 pub mod sha1;
+pub mod sha2;
 pub mod srp;
+pub mod xts;
 
 pub fn xor(s1: Vec<u8>, s2: &Vec<u8>) -> Vec<u8> {
     assert_eq!(s1.len(), s2.len(), "parameters must be the same length");
@@ -43,86 +62,299 @@ pub fn u32_be_bytes(arr: &[u32]) -> Vec<u8> {
     arr.iter().flat_map(|x| x.to_be_bytes().to_vec()).collect()
 }
 
-pub fn aes_ecb_encrypt(plain: &Vec<u8>, key: &Vec<u8>) -> Vec<u8> {
-    assert!(plain.len() % 16 == 0 && key.len() == 16);
-    let aes_enc = aessafe::AesSafe128Encryptor::new(key);
+pub fn aes_ecb_encrypt(plain: &[u8], key: &[u8]) -> Vec<u8> {
+    ecb_encrypt(aes(key).as_ref(), plain)
+}
+
+pub fn aes_ecb_decrypt(cipher: &[u8], key: &[u8]) -> Vec<u8> {
+    ecb_decrypt(aes(key).as_ref(), cipher)
+}
+
+pub fn aes_cbc_encrypt(plain: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    cbc_encrypt(aes(key).as_ref(), plain, iv)
+}
+
+pub fn aes_cbc_decrypt(cipher: &[u8], key: &[u8], iv: &[u8]) -> Vec<u8> {
+    cbc_decrypt(aes(key).as_ref(), cipher, iv)
+}
+
+pub fn ctr_keystream(key: &[u8], nonce: u64, size: usize) -> Vec<u8> {
+    ctr_keystream_with(aes(key).as_ref(), nonce, size)
+}
+
+pub fn aes_ctr(cipher: &[u8], key: &[u8], nonce: u64) -> Vec<u8> {
+    ctr(aes(key).as_ref(), cipher, nonce)
+}
+
+pub fn ecb_encrypt(block_cipher: &dyn BlockCipher, plain: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(plain.len().is_multiple_of(blocksize));
+    plain
+        .chunks(blocksize)
+        .flat_map(|block| block_cipher.encrypt_block(block))
+        .collect()
+}
+
+pub fn ecb_decrypt(block_cipher: &dyn BlockCipher, cipher: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(cipher.len().is_multiple_of(blocksize));
+    cipher
+        .chunks(blocksize)
+        .flat_map(|block| block_cipher.decrypt_block(block))
+        .collect()
+}
+
+pub fn cbc_encrypt(block_cipher: &dyn BlockCipher, plain: &[u8], iv: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(plain.len().is_multiple_of(blocksize));
+    assert!(iv.len() == blocksize);
     let mut cipher = Vec::new();
-    for i in (0..plain.len()).step_by(16) {
-        let mut out = [0; 16];
-        aes_enc.encrypt_block(&plain[i..i + 16], &mut out);
-        cipher.extend_from_slice(out.as_ref());
+    let mut prev_cipher_block = iv.to_vec();
+    for block in plain.chunks(blocksize) {
+        let current_block = xor(block.to_vec(), &prev_cipher_block);
+        prev_cipher_block = block_cipher.encrypt_block(&current_block);
+        cipher.extend(&prev_cipher_block);
     }
     cipher
 }
 
-pub fn aes_ecb_decrypt(cipher: &Vec<u8>, key: &Vec<u8>) -> Vec<u8> {
-    assert!(cipher.len() % 16 == 0 && key.len() == 16);
-    let aes_dec = aessafe::AesSafe128Decryptor::new(key);
+pub fn cbc_decrypt(block_cipher: &dyn BlockCipher, cipher: &[u8], iv: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(cipher.len().is_multiple_of(blocksize) && iv.len() == blocksize);
     let mut plain = Vec::new();
-    for i in (0..cipher.len()).step_by(16) {
-        let mut out = [0; 16];
-        aes_dec.decrypt_block(&cipher[i..i + 16], &mut out);
-        plain.extend_from_slice(out.as_ref());
+    let mut prev_cipher_block = iv;
+    for block in cipher.chunks(blocksize) {
+        plain.extend(xor(
+            block_cipher.decrypt_block(block),
+            &prev_cipher_block.to_vec(),
+        ));
+        prev_cipher_block = block;
     }
     plain
 }
 
-pub fn aes_cbc_encrypt(plain: &Vec<u8>, key: &Vec<u8>, iv: &Vec<u8>) -> Vec<u8> {
-    assert!(plain.len() % 16 == 0);
-    assert!(key.len() == 16);
-    assert!(iv.len() == 16);
-    let aes_enc = aessafe::AesSafe128Encryptor::new(key);
-    let mut cipher = Vec::new();
-    let mut bind_block: Vec<u8>;
+/// CBC with any padding scheme instead of requiring aligned input
+pub fn cbc_encrypt_padded(
+    block_cipher: &dyn BlockCipher,
+    plain: &[u8],
+    iv: &[u8],
+    padding: &dyn Padding,
+) -> Vec<u8> {
+    cbc_encrypt(
+        block_cipher,
+        &padding.pad(plain, block_cipher.block_size()),
+        iv,
+    )
+}
+
+pub fn cbc_decrypt_padded(
+    block_cipher: &dyn BlockCipher,
+    cipher: &[u8],
+    iv: &[u8],
+    padding: &dyn Padding,
+) -> Result<Vec<u8>, CryptoError> {
+    let blocksize = block_cipher.block_size();
+    if cipher.is_empty() || !cipher.len().is_multiple_of(blocksize) {
+        return Err(CryptoError::BlockAlignment { len: cipher.len() });
+    }
+    padding.unpad(&cbc_decrypt(block_cipher, cipher, iv), blocksize)
+}
+
+/// Keystream of `nonce||counter` blocks, both little endian and each filling half a block
+pub fn ctr_keystream_with(block_cipher: &dyn BlockCipher, nonce: u64, size: usize) -> Vec<u8> {
+    // would be nicer as a generator, but it's not yet stable
+    let half = block_cipher.block_size() / 2;
+    assert!(half <= 8, "nonce and counter are at most 64 bits");
+    let mut keystream = Vec::new();
+    for counter in 0..(size / (2 * half)) + 1 {
+        let mut block = nonce.to_le_bytes()[..half].to_vec();
+        block.extend(&(counter as u64).to_le_bytes()[..half]);
+        keystream.extend(block_cipher.encrypt_block(&block));
+    }
+    keystream
+}
+
+pub fn ctr(block_cipher: &dyn BlockCipher, cipher: &[u8], nonce: u64) -> Vec<u8> {
+    cipher
+        .iter()
+        .zip(ctr_keystream_with(block_cipher, nonce, cipher.len()))
+        .map(|(x, y)| x ^ y)
+        .collect()
+}
+
+/// Output feedback: the keystream is the iv encrypted over and over, so en- and decryption are the same
+pub fn ofb(block_cipher: &dyn BlockCipher, data: &[u8], iv: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+    let mut result = Vec::with_capacity(data.len());
+    let mut keystream = iv.to_vec();
+    for chunk in data.chunks(block_cipher.block_size()) {
+        keystream = block_cipher.encrypt_block(&keystream);
+        result.extend(chunk.iter().zip(keystream.iter()).map(|(x, y)| x ^ y));
+    }
+    result
+}
+
+/// Full block cipher feedback, the last block may be partial
+pub fn cfb_encrypt(block_cipher: &dyn BlockCipher, plain: &[u8], iv: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+    let mut cipher = Vec::with_capacity(plain.len());
+    let mut prev_cipher_block = iv.to_vec();
+    for block in plain.chunks(block_cipher.block_size()) {
+        let keystream = block_cipher.encrypt_block(&prev_cipher_block);
+        prev_cipher_block = block.iter().zip(keystream).map(|(x, y)| x ^ y).collect();
+        cipher.extend(&prev_cipher_block);
+    }
+    cipher
+}
+
+pub fn cfb_decrypt(block_cipher: &dyn BlockCipher, cipher: &[u8], iv: &[u8]) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+    let mut plain = Vec::with_capacity(cipher.len());
     let mut prev_cipher_block = iv;
-    for i in (0..plain.len()).step_by(16) {
-        let current_block = xor(plain[i..i + 16].to_vec(), prev_cipher_block);
-        let mut out = [0; 16];
-        aes_enc.encrypt_block(current_block.as_ref(), &mut out);
-        cipher.extend_from_slice(out.as_ref());
-        bind_block = out.to_vec();
-        prev_cipher_block = &bind_block;
+    for block in cipher.chunks(block_cipher.block_size()) {
+        let keystream = block_cipher.encrypt_block(prev_cipher_block);
+        plain.extend(block.iter().zip(keystream).map(|(x, y)| x ^ y));
+        prev_cipher_block = block;
+    }
+    plain
+}
+
+/// Cipher feedback with 8 bit segments: one block encryption per byte, the register shifts in each cipher byte
+fn cfb8(block_cipher: &dyn BlockCipher, data: &[u8], iv: &[u8], decrypt: bool) -> Vec<u8> {
+    assert_eq!(iv.len(), block_cipher.block_size());
+    let mut register = iv.to_vec();
+    let mut result = Vec::with_capacity(data.len());
+    for byte in data {
+        let out = byte ^ block_cipher.encrypt_block(&register)[0];
+        register.remove(0);
+        register.push(if decrypt { *byte } else { out });
+        result.push(out);
+    }
+    result
+}
+
+pub fn cfb8_encrypt(block_cipher: &dyn BlockCipher, plain: &[u8], iv: &[u8]) -> Vec<u8> {
+    cfb8(block_cipher, plain, iv, false)
+}
+
+pub fn cfb8_decrypt(block_cipher: &dyn BlockCipher, cipher: &[u8], iv: &[u8]) -> Vec<u8> {
+    cfb8(block_cipher, cipher, iv, true)
+}
+
+/// Propagating CBC: `c_i = E(p_i ^ p_i-1 ^ c_i-1)`, so a changed block garbles everything after it
+pub fn pcbc_encrypt(block_cipher: &dyn BlockCipher, plain: &[u8], iv: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(plain.len().is_multiple_of(blocksize) && iv.len() == blocksize);
+    let mut cipher = Vec::new();
+    let mut feedback = iv.to_vec();
+    for block in plain.chunks(blocksize) {
+        let cipher_block = block_cipher.encrypt_block(&xor(block.to_vec(), &feedback));
+        feedback = xor(block.to_vec(), &cipher_block);
+        cipher.extend(cipher_block);
     }
     cipher
 }
 
-pub fn aes_cbc_decrypt(cipher: &Vec<u8>, key: &Vec<u8>, iv: &Vec<u8>) -> Vec<u8> {
-    assert!(cipher.len() % 16 == 0 && key.len() == 16 && iv.len() == 16);
-    let aes_dec = aessafe::AesSafe128Decryptor::new(key);
+pub fn pcbc_decrypt(block_cipher: &dyn BlockCipher, cipher: &[u8], iv: &[u8]) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(cipher.len().is_multiple_of(blocksize) && iv.len() == blocksize);
     let mut plain = Vec::new();
-    let mut bind_block: Vec<u8>;
-    let mut prev_cipher_block = iv;
-    for i in (0..cipher.len()).step_by(16) {
-        let mut out = [0; 16];
-        aes_dec.decrypt_block(&cipher[i..i + 16], &mut out);
-        plain.extend(xor(out.to_vec(), &prev_cipher_block));
-        bind_block = cipher[i..i + 16].to_vec();
-        prev_cipher_block = &bind_block;
+    let mut feedback = iv.to_vec();
+    for block in cipher.chunks(blocksize) {
+        let plain_block = xor(block_cipher.decrypt_block(block), &feedback);
+        feedback = xor(plain_block.clone(), &block.to_vec());
+        plain.extend(plain_block);
     }
     plain
 }
 
-pub fn ctr_keystream(key: &Vec<u8>, nonce: u64, size: usize) -> Vec<u8> {
-    // would be nicer as a generator, but it's not yet stable
-    let aes_enc = aessafe::AesSafe128Encryptor::new(key);
-    let mut keystream = Vec::new();
-    for counter in 0..(size / 16) + 1 {
-        let mut block = nonce.to_le_bytes().to_vec();
-        block.extend(&(counter as u64).to_le_bytes());
+/// The NIST variants of CBC with ciphertext stealing (SP 800-38A addendum).
+/// All produce the same bytes, they only differ in the order of the last two blocks.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CiphertextStealing {
+    /// Partial block first, then the last full block
+    Cs1,
+    /// Like `Cs3`, but unchanged CBC if the plaintext is block aligned
+    Cs2,
+    /// Always swap the last two blocks (Kerberos, RFC 3962)
+    Cs3,
+}
 
-        let mut out = [0; 16];
-        aes_enc.encrypt_block(block.as_ref(), &mut out);
-        keystream.extend_from_slice(out.as_ref());
+impl CiphertextStealing {
+    /// Whether the last two blocks are swapped compared to `Cs1`
+    fn swapped(self, partial: usize, blocksize: usize) -> bool {
+        match self {
+            CiphertextStealing::Cs1 => false,
+            CiphertextStealing::Cs2 => partial != blocksize,
+            CiphertextStealing::Cs3 => true,
+        }
     }
-    keystream
 }
 
-pub fn aes_ctr(cipher: &Vec<u8>, key: &Vec<u8>, nonce: u64) -> Vec<u8> {
+/// CBC for any plaintext of at least one block, the ciphertext has the same length
+pub fn cbc_cs_encrypt(
+    block_cipher: &dyn BlockCipher,
+    plain: &[u8],
+    iv: &[u8],
+    variant: CiphertextStealing,
+) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(plain.len() >= blocksize, "needs at least one full block");
+    let partial = blocksize - (blocksize - plain.len() % blocksize) % blocksize;
+    let mut padded = plain.to_vec();
+    padded.resize(plain.len() + blocksize - partial, 0);
+    let mut cipher = cbc_encrypt(block_cipher, &padded, iv);
+    let last = cipher.split_off(cipher.len() - blocksize);
+    if cipher.is_empty() {
+        return last;
+    }
+    // drop the bytes of the second to last block that the last block recovers
+    cipher.truncate(cipher.len() - blocksize + partial);
+    if variant.swapped(partial, blocksize) {
+        let stolen = cipher.split_off(cipher.len() - partial);
+        cipher.extend(last);
+        cipher.extend(stolen);
+    } else {
+        cipher.extend(last);
+    }
     cipher
+}
+
+pub fn cbc_cs_decrypt(
+    block_cipher: &dyn BlockCipher,
+    cipher: &[u8],
+    iv: &[u8],
+    variant: CiphertextStealing,
+) -> Vec<u8> {
+    let blocksize = block_cipher.block_size();
+    assert!(cipher.len() >= blocksize, "needs at least one full block");
+    if cipher.len() == blocksize {
+        return cbc_decrypt(block_cipher, cipher, iv);
+    }
+    let partial = blocksize - (blocksize - cipher.len() % blocksize) % blocksize;
+    let mut cipher = cipher.to_vec();
+    if variant.swapped(partial, blocksize) {
+        let stolen = cipher.split_off(cipher.len() - partial);
+        let last = cipher.split_off(cipher.len() - blocksize);
+        cipher.extend(stolen);
+        cipher.extend(last);
+    }
+    // back to the CS1 layout: C_1 .. C_n-2 || C*_n-1 || C_n
+    let last = cipher.split_off(cipher.len() - blocksize);
+    let stolen = cipher.split_off(cipher.len() - partial);
+    // D(C_n) = (P*_n || 0) ^ C_n-1, so its tail is the missing tail of C_n-1
+    let decrypted_last = block_cipher.decrypt_block(&last);
+    let mut second_to_last = stolen.clone();
+    second_to_last.extend(&decrypted_last[partial..]);
+    let tail: Vec<u8> = decrypted_last[..partial]
         .iter()
-        .zip(ctr_keystream(&key, nonce, cipher.len()))
+        .zip(stolen.iter())
         .map(|(x, y)| x ^ y)
-        .collect()
+        .collect();
+    cipher.extend(second_to_last);
+    let mut plain = cbc_decrypt(block_cipher, &cipher, iv);
+    plain.extend(tail);
+    plain
 }
 
 // DECRYPT
@@ -209,7 +441,7 @@ pub fn extract_fixed_suffix(
             prefix.push(c);
             // compare only useful block = the one containing the border between prefix and secret
             // previous blocks are equal due to construction, blocks after contain an additional copy of secret
-            let working_block = (found_blocks * 16)..((found_blocks + 1) * 16);
+            let working_block = (found_blocks * blocksize)..((found_blocks + 1) * blocksize);
             if blackbox(prefix.clone()).get(working_block.clone()).unwrap()
                 == to_match.get(working_block).unwrap()
             {
@@ -319,6 +551,23 @@ pub enum CryptoError {
         padding: u8,
         last_removed: Option<u8>,
     },
+    /// The data to unpad is empty or not a multiple of the blocksize
+    BlockAlignment { len: usize },
+    /// Length byte out of range or a non-zero filler byte
+    AnsiX923Padding {
+        padding: u8,
+        last_removed: Option<u8>,
+    },
+    /// Length byte out of range, the filler is random and not checked
+    Iso10126Padding { padding: u8 },
+    /// No `0x80` marker before the trailing zeros, `found` is the first non-zero byte
+    Iso7816Padding { found: Option<u8> },
+    /// A whole block or more of trailing zeros, zero padding never adds that many
+    ZeroPadding { zeros: usize },
+    /// A MAC or authentication tag did not match
+    Authentication,
+    /// An unwrapped key failed the integrity check value (or its length/padding for RFC 5649)
+    KeyWrapIntegrity,
 }
 
 /// Prepend a random, random length prefix
@@ -401,6 +650,12 @@ pub fn compare(a: &Vec<u8>, b: &Vec<u8>) -> bool {
     a.len() == b.len()
 }
 
+/// Compare MACs and tags in constant time, we have seen what happens otherwise in s4c31.
+/// Only a difference in length returns early.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 /// Tries progressively longer plain texts, until there is a new block added.
 /// The difference between the previous and the new length is the blocksize.
 /// Maximum blocksize detected is 64 bytes.
@@ -419,8 +674,8 @@ pub fn detect_blocksize(blackbox: &mut dyn Fn(Vec<u8>) -> Vec<u8>) -> Option<usi
 pub fn detect_ecb(blackbox: &mut dyn Fn(Vec<u8>) -> Vec<u8>, blocksize: usize) -> bool {
     let cipher = blackbox(vec![0; 3 * blocksize]);
     let mut blocks = HashSet::new();
-    for i in (0..cipher.len()).step_by(16) {
-        let block = cipher.get(i..i + 16);
+    for i in (0..cipher.len()).step_by(blocksize) {
+        let block = cipher.get(i..i + blocksize);
         match block {
             Some(b) => {
                 if blocks.contains(&b) {
@@ -446,6 +701,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn constant_time_comparison() {
+        assert!(constant_time_eq(b"tag", b"tag"));
+        assert!(!constant_time_eq(b"tag", b"taG"));
+        assert!(!constant_time_eq(b"tag", b"ta"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
     #[test]
     fn aes_ecb() {
         let plain = b"YELLOW SUBMARINEYELLOW SUBMARINE".to_vec();
@@ -475,6 +738,216 @@ mod tests {
         assert_eq!(aes_cbc_decrypt(&cipher, &key, &iv), plain, "plain");
     }
 
+    #[test]
+    fn aes_256_modes() {
+        let plain = b"YELLOW SUBMARINEYELLOW SUBMARINE".to_vec();
+        let key = b"YELLOW SUBMARINEYELLOW SUBMARINE".to_vec();
+        let iv = random_128_bit();
+        assert_eq!(aes_ecb_decrypt(&aes_ecb_encrypt(&plain, &key), &key), plain);
+        assert_eq!(
+            aes_cbc_decrypt(&aes_cbc_encrypt(&plain, &key, &iv), &key, &iv),
+            plain
+        );
+        assert_eq!(aes_ctr(&aes_ctr(&plain, &key, 3), &key, 3), plain);
+        assert_ne!(
+            aes_ecb_encrypt(&plain, &key),
+            aes_ecb_encrypt(&plain, &key[..16].to_vec())
+        );
+    }
+
+    /// Not a cipher anyone should use, but it has a different blocksize
+    struct XorCipher(Vec<u8>);
+
+    impl BlockCipher for XorCipher {
+        fn block_size(&self) -> usize {
+            self.0.len()
+        }
+        fn encrypt_block(&self, block: &[u8]) -> Vec<u8> {
+            xor(block.to_vec(), &self.0).iter().rev().cloned().collect()
+        }
+        fn decrypt_block(&self, block: &[u8]) -> Vec<u8> {
+            xor(block.iter().rev().cloned().collect(), &self.0)
+        }
+    }
+
+    #[test]
+    fn attacks_with_other_blocksize() {
+        let toy = XorCipher(b"8 bytes!".to_vec());
+        let secret = b"Rollin' in my 5.0".to_vec();
+        let mut blackbox = |mut plain: Vec<u8>| {
+            plain.extend(&secret);
+            ecb_encrypt(&toy, &pad_pkcs7(plain, 8))
+        };
+        let blocksize = detect_blocksize(&mut blackbox).unwrap();
+        assert_eq!(blocksize, 8);
+        assert!(detect_ecb(&mut blackbox, blocksize));
+        assert_eq!(extract_fixed_suffix(&mut blackbox, blocksize), secret);
+
+        let iv = b"iv iv iv".to_vec();
+        let cipher = cbc_encrypt(&toy, &pad_pkcs7(secret.clone(), 8), &iv);
+        assert_eq!(unpad_pkcs7(cbc_decrypt(&toy, &cipher, &iv)), Ok(secret));
+    }
+
+    /// NIST SP 800-38A, F.3 and F.4 with AES-128
+    #[test]
+    fn feedback_modes_sp_800_38a() {
+        let aes = aes(&hex::decode("2b7e151628aed2a6abf7158809cf4f3c").unwrap());
+        let iv = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let plain = hex::decode(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        )
+        .unwrap();
+
+        let cipher = cfb8_encrypt(aes.as_ref(), &plain[..18], &iv);
+        assert_eq!(hex::encode(&cipher), "3b79424c9c0dd436bace9e0ed4586a4f32b9");
+        assert_eq!(
+            cfb8_decrypt(aes.as_ref(), &cipher, &iv),
+            plain[..18].to_vec()
+        );
+
+        let cipher = cfb_encrypt(aes.as_ref(), &plain, &iv);
+        assert_eq!(
+            hex::encode(&cipher),
+            "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+             26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6"
+        );
+        assert_eq!(cfb_decrypt(aes.as_ref(), &cipher, &iv), plain);
+
+        let cipher = ofb(aes.as_ref(), &plain, &iv);
+        assert_eq!(
+            hex::encode(&cipher),
+            "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+             9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"
+        );
+        assert_eq!(ofb(aes.as_ref(), &cipher, &iv), plain);
+
+        // partial last blocks are fine for the stream-like modes
+        assert_eq!(
+            cfb_decrypt(
+                aes.as_ref(),
+                &cfb_encrypt(aes.as_ref(), &plain[..20], &iv),
+                &iv
+            ),
+            plain[..20].to_vec()
+        );
+        assert_eq!(ofb(aes.as_ref(), &plain[..20], &iv), cipher[..20].to_vec());
+    }
+
+    #[test]
+    fn cbc_with_any_padding() {
+        let aes = aes(&random_128_bit());
+        let iv = random_128_bit();
+        let plain = b"ICE ICE BABY".to_vec();
+        let schemes: Vec<Box<dyn Padding>> = vec![
+            Box::new(padding::Pkcs7),
+            Box::new(padding::AnsiX923),
+            Box::new(padding::Iso10126),
+            Box::new(padding::Iso7816),
+            Box::new(padding::Zero),
+        ];
+        for scheme in schemes.iter() {
+            let cipher = cbc_encrypt_padded(aes.as_ref(), &plain, &iv, scheme.as_ref());
+            assert_eq!(cipher.len(), 16);
+            assert_eq!(
+                cbc_decrypt_padded(aes.as_ref(), &cipher, &iv, scheme.as_ref()),
+                Ok(plain.clone())
+            );
+            assert_eq!(
+                cbc_decrypt_padded(aes.as_ref(), &cipher[..15], &iv, scheme.as_ref()),
+                Err(CryptoError::BlockAlignment { len: 15 })
+            );
+        }
+    }
+
+    /// RFC 3962 appendix B, AES-128 CBC-CS3 with a zero iv
+    #[test]
+    fn ciphertext_stealing_rfc_3962() {
+        let aes = aes(b"chicken teriyaki");
+        let iv = [0; 16];
+        let plain = b"I would like the General Gau's Chicken, please, and wonton soup.";
+        let cases = [
+            (17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+            (
+                31,
+                "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5",
+            ),
+            (
+                32,
+                "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584",
+            ),
+            (
+                64,
+                "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8\
+                 4807efe836ee89a526730dbc2f7bc8409dad8bbb96c4cdc03bc103e1a194bbd8",
+            ),
+        ];
+        for (len, expected) in cases.iter() {
+            let cipher = cbc_cs_encrypt(aes.as_ref(), &plain[..*len], &iv, CiphertextStealing::Cs3);
+            assert_eq!(hex::encode(&cipher), *expected, "{}", len);
+            assert_eq!(
+                cbc_cs_decrypt(aes.as_ref(), &cipher, &iv, CiphertextStealing::Cs3),
+                plain[..*len].to_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn ciphertext_stealing_variants() {
+        let aes = aes(&random_128_bit());
+        let iv = random_128_bit();
+        let plain = b"YELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINE".to_vec();
+        let variants = [
+            CiphertextStealing::Cs1,
+            CiphertextStealing::Cs2,
+            CiphertextStealing::Cs3,
+        ];
+        for len in 16..=plain.len() {
+            for variant in variants.iter() {
+                let cipher = cbc_cs_encrypt(aes.as_ref(), &plain[..len], &iv, *variant);
+                assert_eq!(cipher.len(), len);
+                assert_eq!(
+                    cbc_cs_decrypt(aes.as_ref(), &cipher, &iv, *variant),
+                    plain[..len].to_vec(),
+                    "{:?} {}",
+                    variant,
+                    len
+                );
+            }
+        }
+        // block aligned, CS1 and CS2 are plain CBC
+        let cbc = cbc_encrypt(aes.as_ref(), &plain, &iv);
+        assert_eq!(
+            cbc_cs_encrypt(aes.as_ref(), &plain, &iv, CiphertextStealing::Cs1),
+            cbc
+        );
+        assert_eq!(
+            cbc_cs_encrypt(aes.as_ref(), &plain, &iv, CiphertextStealing::Cs2),
+            cbc
+        );
+        // not aligned, CS2 and CS3 agree
+        assert_eq!(
+            cbc_cs_encrypt(aes.as_ref(), &plain[..40], &iv, CiphertextStealing::Cs2),
+            cbc_cs_encrypt(aes.as_ref(), &plain[..40], &iv, CiphertextStealing::Cs3)
+        );
+    }
+
+    #[test]
+    fn pcbc_propagates_errors() {
+        let aes = aes(&random_128_bit());
+        let iv = random_128_bit();
+        let plain = b"YELLOW SUBMARINEYELLOW SUBMARINEYELLOW SUBMARINE".to_vec();
+        let mut cipher = pcbc_encrypt(aes.as_ref(), &plain, &iv);
+        assert_eq!(pcbc_decrypt(aes.as_ref(), &cipher, &iv), plain);
+        assert_ne!(cipher, cbc_encrypt(aes.as_ref(), &plain, &iv));
+
+        cipher[0] ^= 1;
+        let garbled = pcbc_decrypt(aes.as_ref(), &cipher, &iv);
+        for (garbled_block, block) in garbled.chunks(16).zip(plain.chunks(16)) {
+            assert_ne!(garbled_block, block);
+        }
+    }
+
     #[test]
     fn converting_u32_to_vec() {
         assert_eq!(
@@ -1,10 +1,12 @@
 //! # Implement SHA1, trying to proxy the std one first
 //! learned: <<1 != rotate_left -> wrapping vs not
 
+use crate::digest::{update_blocks, Digest, State};
+
 const STARTING_STATE: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]; // le
 
 pub struct MyMd4 {
-    h: [u32; 4],
+    state: State<[u32; 4]>,
 }
 
 impl MyMd4 {
@@ -13,8 +15,8 @@ impl MyMd4 {
     pub fn padding(len: usize) -> Vec<u8> {
         let mut padding = vec![0x80];
         let mut len_bits = len * 8;
-        // might be off by one
-        if len_bits % 512 > 448 {
+        // no room left for the length, not even when exactly 448 bits are used
+        if len_bits % 512 >= 448 {
             len_bits += 72;
             padding.append(&mut vec![0; 9]);
         }
@@ -53,13 +55,13 @@ impl MyMd4 {
             0,
             "input length has to be a multiple of 512 bits"
         );
-        let mut sha1 = MyMd4 { h: state };
+        let mut h = state;
         for i in (0..input.len()).step_by(64) {
             let mut block: [u8; 64] = [0; 64];
             block.copy_from_slice(&input[i..i + 64]);
-            do_block(&mut sha1, block);
+            do_block(&mut h, &block);
         }
-        sha1.h
+        h
     }
 
     /// Perform Sha1(key||data)
@@ -77,7 +79,58 @@ impl MyMd4 {
     }
 }
 
-fn do_block(md4: &mut MyMd4, block: [u8; 64]) {
+impl Digest for MyMd4 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_SIZE: usize = 16;
+    type Words = [u32; 4];
+    type Output = [u8; 16];
+
+    fn new() -> Self {
+        MyMd4::import_state(State {
+            h: STARTING_STATE,
+            len: 0,
+            buffer: vec![],
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        update_blocks(&mut self.state, data, 64, do_block);
+    }
+
+    fn finalize(mut self) -> Self::Output {
+        let padding = MyMd4::padding(self.state.len as usize);
+        self.update(&padding);
+        let mut output = [0; 16];
+        for (chunk, word) in output.chunks_mut(4).zip(self.state.h.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        output
+    }
+
+    fn export_state(&self) -> State<Self::Words> {
+        self.state.clone()
+    }
+
+    fn import_state(state: State<Self::Words>) -> Self {
+        MyMd4 { state }
+    }
+
+    fn padding(len: usize) -> Vec<u8> {
+        MyMd4::padding(len)
+    }
+
+    fn words_from_output(output: &Self::Output) -> Option<Self::Words> {
+        let mut words = [0; 4];
+        for (word, chunk) in words.iter_mut().zip(output.chunks(4)) {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(chunk);
+            *word = u32::from_le_bytes(bytes);
+        }
+        Some(words)
+    }
+}
+
+fn do_block(state: &mut [u32; 4], block: &[u8]) {
     fn f(x: u32, y: u32, z: u32) -> u32 {
         (x & y) | (!x & z)
     }
@@ -123,10 +176,10 @@ fn do_block(md4: &mut MyMd4, block: [u8; 64]) {
     //     x[i / 4] = x[i / 4] | tmp;
     // }
 
-    let mut a = md4.h[0];
-    let mut b = md4.h[1];
-    let mut c = md4.h[2];
-    let mut d = md4.h[3];
+    let mut a = state[0];
+    let mut b = state[1];
+    let mut c = state[2];
+    let mut d = state[3];
 
     for &i in &[0, 4, 8, 12] {
         round_1(&mut a, b, c, d, x[i + 0], 3);
@@ -152,10 +205,10 @@ fn do_block(md4: &mut MyMd4, block: [u8; 64]) {
         round_3(&mut b, c, d, a, x[12 + i], 15);
     }
 
-    md4.h[0] = md4.h[0].wrapping_add(a);
-    md4.h[1] = md4.h[1].wrapping_add(b);
-    md4.h[2] = md4.h[2].wrapping_add(c);
-    md4.h[3] = md4.h[3].wrapping_add(d);
+    state[0] = state[0].wrapping_add(a);
+    state[1] = state[1].wrapping_add(b);
+    state[2] = state[2].wrapping_add(c);
+    state[3] = state[3].wrapping_add(d);
     // println!("{:x?}", sha1.h);
 }
 
@@ -208,6 +261,9 @@ mod tests {
             "last padding byte"
         );
         assert_eq!(pad.len(), 64 * 2 - 57, "length");
+
+        // exactly 448 bits, the length does not fit either
+        assert_eq!(MyMd4::padding(56).len(), 64 * 2 - 56, "length");
     }
 
     #[test]
@@ -219,4 +275,33 @@ mod tests {
         assert!(!MyMd4::validate_mac(&b"123".to_vec(), &data, &mac));
         assert!(!MyMd4::validate_mac(&key, &b"new data".to_vec(), &mac));
     }
+
+    #[test]
+    fn streaming_digest() {
+        assert_eq!(
+            hex::encode(MyMd4::digest(b"abc")),
+            "a448017aaf21d8525fc10ae87aa6729d"
+        );
+        // 56 bytes, the length has to go into an extra block
+        assert_eq!(
+            hex::encode(MyMd4::digest(&[b'a'; 56])),
+            "d5f9a9e9257077a5f08b0b92f348b0ad"
+        );
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut streaming = MyMd4::new();
+        for chunk in data.chunks(7) {
+            streaming.update(chunk);
+        }
+        assert_eq!(streaming.finalize(), MyMd4::digest(&data));
+
+        // stop in the middle of a block and continue somewhere else
+        let mut first = MyMd4::new();
+        first.update(&data[..100]);
+        let state = first.export_state();
+        assert_eq!(state.len, 100);
+        assert_eq!(state.buffer.len(), 100 % 64);
+        let mut second = MyMd4::import_state(state);
+        second.update(&data[100..]);
+        assert_eq!(second.finalize(), MyMd4::digest(&data));
+    }
 }
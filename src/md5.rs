@@ -0,0 +1,274 @@
+//! # MD5 (RFC 1321)
+//! Little endian Merkle–Damgård like MD4, with a fourth round and a different constant for every step.
+//! `keyed_mac` is as length-extendable as the MD4 one (s4c30, `attacks::length_extension`),
+//! and it is not collision resistant: the tests check the first published collision (Wang et al. 2004)
+//! and that it survives any common suffix.
+
+use crate::digest::{update_blocks, Digest, State};
+
+const STARTING_STATE: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+/// `floor(abs(sin(i + 1)) * 2^32)`
+const K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+/// Left rotations, four per round
+const SHIFTS: [[u32; 4]; 4] = [
+    [7, 12, 17, 22],
+    [5, 9, 14, 20],
+    [4, 11, 16, 23],
+    [6, 10, 15, 21],
+];
+
+pub struct MyMd5 {
+    state: State<[u32; 4]>,
+}
+
+impl MyMd5 {
+    /// Obtain only the padding for a certain input length in bytes
+    pub fn padding(len: usize) -> Vec<u8> {
+        MyMd5::padding_with_size(len, len)
+    }
+
+    /// The padding after `len` bytes, claiming the message is `size` bytes long
+    fn padding_with_size(len: usize, size: usize) -> Vec<u8> {
+        let mut padding = vec![0x80];
+        while (len + padding.len()) % 64 != 56 {
+            padding.push(0);
+        }
+        padding.extend_from_slice(&((size * 8) as u64).to_le_bytes());
+        padding
+    }
+
+    pub fn pad(mut input: Vec<u8>) -> Vec<u8> {
+        input.append(&mut MyMd5::padding(input.len()));
+        input
+    }
+
+    /// Pad `input` as if it was `size` bytes long
+    pub fn pad_fake_size(mut input: Vec<u8>, size: usize) -> Vec<u8> {
+        input.append(&mut MyMd5::padding_with_size(input.len(), size));
+        input
+    }
+
+    /// Hash input vector, assumes padding has been applied already
+    pub fn hash(input: Vec<u8>) -> [u32; 4] {
+        MyMd5::hash_with_initial_state(STARTING_STATE, input)
+    }
+
+    /// Hash input vector, padding is handled
+    pub fn hash_padded(input: Vec<u8>) -> [u32; 4] {
+        MyMd5::hash(MyMd5::pad(input))
+    }
+
+    /// Hash input vector with a specific starting state, assumes padding has been applied already
+    pub fn hash_with_initial_state(state: [u32; 4], input: Vec<u8>) -> [u32; 4] {
+        assert_eq!(
+            input.len() % 64,
+            0,
+            "input length has to be a multiple of 512 bits"
+        );
+        let mut h = state;
+        for block in input.chunks(64) {
+            do_block(&mut h, block);
+        }
+        h
+    }
+
+    /// Perform Md5(key||data)
+    pub fn keyed_mac(key: &[u8], data: &[u8]) -> [u32; 4] {
+        MyMd5::hash_padded([key, data].concat())
+    }
+
+    /// validate that Md5(key||data) == mac
+    pub fn validate_mac(key: &[u8], data: &[u8], mac: &[u32; 4]) -> bool {
+        MyMd5::keyed_mac(key, data) == *mac
+    }
+}
+
+impl Digest for MyMd5 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_SIZE: usize = 16;
+    type Words = [u32; 4];
+    type Output = [u8; 16];
+
+    fn new() -> Self {
+        MyMd5::import_state(State {
+            h: STARTING_STATE,
+            len: 0,
+            buffer: vec![],
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        update_blocks(&mut self.state, data, 64, do_block);
+    }
+
+    fn finalize(mut self) -> Self::Output {
+        let padding = MyMd5::padding(self.state.len as usize);
+        self.update(&padding);
+        let mut output = [0; 16];
+        for (chunk, word) in output.chunks_mut(4).zip(self.state.h.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        output
+    }
+
+    fn export_state(&self) -> State<Self::Words> {
+        self.state.clone()
+    }
+
+    fn import_state(state: State<Self::Words>) -> Self {
+        MyMd5 { state }
+    }
+
+    fn padding(len: usize) -> Vec<u8> {
+        MyMd5::padding(len)
+    }
+
+    fn words_from_output(output: &Self::Output) -> Option<Self::Words> {
+        let mut words = [0; 4];
+        for (word, chunk) in words.iter_mut().zip(output.chunks(4)) {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(chunk);
+            *word = u32::from_le_bytes(bytes);
+        }
+        Some(words)
+    }
+}
+
+fn do_block(h: &mut [u32; 4], block: &[u8]) {
+    let mut x = [0u32; 16];
+    for (word, chunk) in x.iter_mut().zip(block.chunks(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    let [mut a, mut b, mut c, mut d] = *h;
+    for i in 0..64 {
+        let (f, k) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let tmp = a
+            .wrapping_add(f)
+            .wrapping_add(K[i])
+            .wrapping_add(x[k])
+            .rotate_left(SHIFTS[i / 16][i % 4]);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(tmp);
+    }
+
+    h[0] = h[0].wrapping_add(a);
+    h[1] = h[1].wrapping_add(b);
+    h[2] = h[2].wrapping_add(c);
+    h[3] = h[3].wrapping_add(d);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attacks::length_extension;
+    use crate::random_128_bit;
+
+    #[test]
+    fn correct_padding() {
+        assert_eq!(MyMd5::padding(55).len(), 9);
+        // exactly 448 bits, the length does not fit either
+        assert_eq!(MyMd5::padding(56).len(), 64 * 2 - 56);
+        let padded = MyMd5::pad_fake_size(vec![0; 56], 120);
+        assert_eq!(padded.len(), 128);
+        assert_eq!(padded[120..], ((120 * 8) as u64).to_le_bytes());
+    }
+
+    /// The test suite of RFC 1321
+    #[test]
+    fn rfc_1321() {
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", "d41d8cd98f00b204e9800998ecf8427e"),
+            (b"a", "0cc175b9c0f1b6a831c399e269772661"),
+            (b"abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (b"message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+            (
+                b"abcdefghijklmnopqrstuvwxyz",
+                "c3fcd3d76192e4007dfb496cca67e13b",
+            ),
+            (
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "d174ab98d277d9f5a5611c2c9f419d9f",
+            ),
+            (
+                b"12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "57edf4a22be3c955ac49da2e2107b67a",
+            ),
+        ];
+        for (input, expected) in vectors.iter() {
+            assert_eq!(hex::encode(MyMd5::digest(input)), *expected);
+        }
+        assert_eq!(
+            MyMd5::hash_padded(b"abc".to_vec()),
+            MyMd5::words_from_output(&MyMd5::digest(b"abc")).unwrap()
+        );
+    }
+
+    #[test]
+    fn length_extension() {
+        let key = random_128_bit();
+        let data = b"comment1=cooking%20MCs;userdata=foo;comment2=%20like%20a%20pound%20of%20bacon";
+        let mac = MyMd5::keyed_mac(&key, data);
+
+        let mut output = [0; 16];
+        for (chunk, word) in output.chunks_mut(4).zip(mac.iter()) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        let oracle = |message: &[u8], mac: &[u8; 16]| {
+            MyMd5::validate_mac(&key, message, &MyMd5::words_from_output(mac).unwrap())
+        };
+        let (forged, _) =
+            length_extension::forge::<MyMd5>(data, &output, b";admin=true", 64, &oracle).unwrap();
+        assert!(forged.ends_with(b";admin=true"));
+    }
+
+    /// The first published collision (Wang, Feng, Lai, Yu 2004), two blocks that differ in six bytes
+    #[test]
+    fn collision() {
+        let first = hex::decode(
+            "d131dd02c5e6eec4693d9a0698aff95c2fcab58712467eab4004583eb8fb7f89\
+             55ad340609f4b30283e488832571415a085125e8f7cdc99fd91dbdf280373c5b\
+             d8823e3156348f5bae6dacd436c919c6dd53e2b487da03fd02396306d248cda0\
+             e99f33420f577ee8ce54b67080a80d1ec69821bcb6a8839396f9652b6ff72a70",
+        )
+        .unwrap();
+        let second = hex::decode(
+            "d131dd02c5e6eec4693d9a0698aff95c2fcab50712467eab4004583eb8fb7f89\
+             55ad340609f4b30283e4888325f1415a085125e8f7cdc99fd91dbd7280373c5b\
+             d8823e3156348f5bae6dacd436c919c6dd53e23487da03fd02396306d248cda0\
+             e99f33420f577ee8ce54b67080280d1ec69821bcb6a8839396f965ab6ff72a70",
+        )
+        .unwrap();
+        assert_ne!(first, second);
+        assert_eq!(
+            hex::encode(MyMd5::digest(&first)),
+            "79054025255fb1a26e4bc422aef54eb4"
+        );
+        assert_eq!(MyMd5::digest(&first), MyMd5::digest(&second));
+
+        // both end in the same state, so any common suffix keeps the collision
+        let suffix = b"and this is appended to both";
+        assert_eq!(
+            MyMd5::digest(&[&first[..], suffix].concat()),
+            MyMd5::digest(&[&second[..], suffix].concat())
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! # Padding schemes for block cipher modes
+//! All of them fill the last block up to the blocksize, they differ in what the filler looks like
+//! and so in how much an unpad failure tells about the plaintext:
+//! - PKCS#7: `n` bytes of value `n`
+//! - ANSI X.923: zeros followed by the length byte
+//! - ISO 10126: random bytes followed by the length byte, only the length can be checked
+//! - ISO/IEC 7816-4: a `0x80` marker followed by zeros (bit padding)
+//! - zero padding: zeros, only if needed, and ambiguous for data that ends in zeros
+//!
+//! The strict unpad functions reject everything `pad` does not produce.
+//! Every distinguishable rejection is a padding oracle, see s3c17.
+
+use rand::Rng;
+
+use crate::{pad_pkcs7, unpad_pkcs7, CryptoError};
+
+pub trait Padding {
+    /// Pad to a multiple of `blocksize`
+    fn pad(&self, data: &[u8], blocksize: usize) -> Vec<u8>;
+    /// Remove the padding, failing if it is not exactly what `pad` would have added
+    fn unpad(&self, data: &[u8], blocksize: usize) -> Result<Vec<u8>, CryptoError>;
+    /// The `len` bytes a block has to end with so that unpad accepts it only because of them.
+    /// This is what a padding oracle attack aims for, `None` if the scheme does not check that many bytes.
+    fn oracle_suffix(&self, len: usize, blocksize: usize) -> Option<Vec<u8>>;
+}
+
+pub struct Pkcs7;
+pub struct AnsiX923;
+pub struct Iso10126;
+pub struct Iso7816;
+pub struct Zero;
+
+fn check_alignment(data: &[u8], blocksize: usize) -> Result<(), CryptoError> {
+    if data.is_empty() || !data.len().is_multiple_of(blocksize) {
+        return Err(CryptoError::BlockAlignment { len: data.len() });
+    }
+    Ok(())
+}
+
+fn missing_bytes(data: &[u8], blocksize: usize) -> usize {
+    assert!(
+        blocksize > 0 && blocksize < 256,
+        "length has to fit into a byte"
+    );
+    blocksize - data.len() % blocksize
+}
+
+impl Padding for Pkcs7 {
+    fn pad(&self, data: &[u8], blocksize: usize) -> Vec<u8> {
+        assert!(
+            blocksize > 0 && blocksize < 256,
+            "length has to fit into a byte"
+        );
+        pad_pkcs7(data.to_vec(), blocksize as u8)
+    }
+
+    fn unpad(&self, data: &[u8], blocksize: usize) -> Result<Vec<u8>, CryptoError> {
+        check_alignment(data, blocksize)?;
+        let padding = *data.last().unwrap();
+        if padding as usize > blocksize {
+            return Err(CryptoError::Pkcs7Padding {
+                padding,
+                last_removed: None,
+            });
+        }
+        unpad_pkcs7(data.to_vec())
+    }
+
+    fn oracle_suffix(&self, len: usize, _blocksize: usize) -> Option<Vec<u8>> {
+        Some(vec![len as u8; len])
+    }
+}
+
+impl Padding for AnsiX923 {
+    fn pad(&self, data: &[u8], blocksize: usize) -> Vec<u8> {
+        let missing = missing_bytes(data, blocksize);
+        let mut padded = data.to_vec();
+        padded.extend(vec![0; missing - 1]);
+        padded.push(missing as u8);
+        padded
+    }
+
+    fn unpad(&self, data: &[u8], blocksize: usize) -> Result<Vec<u8>, CryptoError> {
+        check_alignment(data, blocksize)?;
+        let padding = *data.last().unwrap();
+        if padding == 0 || padding as usize > blocksize {
+            return Err(CryptoError::AnsiX923Padding {
+                padding,
+                last_removed: None,
+            });
+        }
+        let start = data.len() - padding as usize;
+        if let Some(filler) = data[start..data.len() - 1].iter().rev().find(|b| **b != 0) {
+            return Err(CryptoError::AnsiX923Padding {
+                padding,
+                last_removed: Some(*filler),
+            });
+        }
+        Ok(data[..start].to_vec())
+    }
+
+    fn oracle_suffix(&self, len: usize, _blocksize: usize) -> Option<Vec<u8>> {
+        let mut suffix = vec![0; len - 1];
+        suffix.push(len as u8);
+        Some(suffix)
+    }
+}
+
+impl Padding for Iso10126 {
+    fn pad(&self, data: &[u8], blocksize: usize) -> Vec<u8> {
+        let missing = missing_bytes(data, blocksize);
+        let mut rng = rand::thread_rng();
+        let mut padded = data.to_vec();
+        padded.extend((1..missing).map(|_| rng.gen::<u8>()));
+        padded.push(missing as u8);
+        padded
+    }
+
+    fn unpad(&self, data: &[u8], blocksize: usize) -> Result<Vec<u8>, CryptoError> {
+        check_alignment(data, blocksize)?;
+        let padding = *data.last().unwrap();
+        if padding == 0 || padding as usize > blocksize {
+            return Err(CryptoError::Iso10126Padding { padding });
+        }
+        Ok(data[..data.len() - padding as usize].to_vec())
+    }
+
+    fn oracle_suffix(&self, _len: usize, _blocksize: usize) -> Option<Vec<u8>> {
+        // the filler is never checked and every last byte in 1..=blocksize is accepted,
+        // so the oracle only tells a range and never a single byte
+        None
+    }
+}
+
+impl Padding for Iso7816 {
+    fn pad(&self, data: &[u8], blocksize: usize) -> Vec<u8> {
+        let missing = missing_bytes(data, blocksize);
+        let mut padded = data.to_vec();
+        padded.push(0x80);
+        padded.extend(vec![0; missing - 1]);
+        padded
+    }
+
+    fn unpad(&self, data: &[u8], blocksize: usize) -> Result<Vec<u8>, CryptoError> {
+        check_alignment(data, blocksize)?;
+        let last_block = &data[data.len() - blocksize..];
+        match last_block.iter().rposition(|b| *b != 0) {
+            Some(i) if last_block[i] == 0x80 => Ok(data[..data.len() - blocksize + i].to_vec()),
+            Some(i) => Err(CryptoError::Iso7816Padding {
+                found: Some(last_block[i]),
+            }),
+            None => Err(CryptoError::Iso7816Padding { found: None }),
+        }
+    }
+
+    fn oracle_suffix(&self, len: usize, _blocksize: usize) -> Option<Vec<u8>> {
+        let mut suffix = vec![0x80];
+        suffix.extend(vec![0; len - 1]);
+        Some(suffix)
+    }
+}
+
+impl Padding for Zero {
+    fn pad(&self, data: &[u8], blocksize: usize) -> Vec<u8> {
+        let missing = missing_bytes(data, blocksize) % blocksize;
+        let mut padded = data.to_vec();
+        padded.extend(vec![0; missing]);
+        padded
+    }
+
+    fn unpad(&self, data: &[u8], blocksize: usize) -> Result<Vec<u8>, CryptoError> {
+        check_alignment(data, blocksize)?;
+        let zeros = data.iter().rev().take_while(|b| **b == 0).count();
+        if zeros >= blocksize {
+            return Err(CryptoError::ZeroPadding { zeros });
+        }
+        Ok(data[..data.len() - zeros].to_vec())
+    }
+
+    fn oracle_suffix(&self, _len: usize, _blocksize: usize) -> Option<Vec<u8>> {
+        // only a block of all zeros is rejected, that does not single out one byte
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schemes() -> Vec<Box<dyn Padding>> {
+        vec![
+            Box::new(Pkcs7),
+            Box::new(AnsiX923),
+            Box::new(Iso10126),
+            Box::new(Iso7816),
+            Box::new(Zero),
+        ]
+    }
+
+    #[test]
+    fn roundtrip() {
+        let data = b"YELLOW SUBMARINE, ICE ICE BABY".to_vec();
+        for scheme in schemes().iter() {
+            for len in 0..data.len() {
+                let padded = scheme.pad(&data[..len], 16);
+                assert!(padded.len().is_multiple_of(16) && padded.len() >= len);
+                if len > 0 {
+                    assert_eq!(scheme.unpad(&padded, 16), Ok(data[..len].to_vec()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn padding_bytes() {
+        let data = b"ICE ICE BABY";
+        assert_eq!(
+            AnsiX923.pad(data, 16),
+            b"ICE ICE BABY\x00\x00\x00\x04".to_vec()
+        );
+        assert_eq!(
+            Iso7816.pad(data, 16),
+            b"ICE ICE BABY\x80\x00\x00\x00".to_vec()
+        );
+        assert_eq!(Zero.pad(data, 16), b"ICE ICE BABY\x00\x00\x00\x00".to_vec());
+        assert_eq!(
+            Zero.pad(b"YELLOW SUBMARINE", 16),
+            b"YELLOW SUBMARINE".to_vec()
+        );
+        let random = Iso10126.pad(data, 16);
+        assert_eq!(random[..12], data[..]);
+        assert_eq!(random[15], 4);
+    }
+
+    #[test]
+    fn strict_unpad() {
+        assert_eq!(
+            Pkcs7.unpad(b"ICE ICE BABY\x04\x04\x04", 16),
+            Err(CryptoError::BlockAlignment { len: 15 })
+        );
+        assert_eq!(
+            Pkcs7.unpad(&[0x11; 32], 16),
+            Err(CryptoError::Pkcs7Padding {
+                padding: 0x11,
+                last_removed: None
+            })
+        );
+        assert_eq!(
+            AnsiX923.unpad(b"ICE ICE BABY\x00\x01\x00\x04", 16),
+            Err(CryptoError::AnsiX923Padding {
+                padding: 4,
+                last_removed: Some(1)
+            })
+        );
+        assert_eq!(
+            Iso10126.unpad(b"ICE ICE BABY\x00\x01\x00\x14", 16),
+            Err(CryptoError::Iso10126Padding { padding: 0x14 })
+        );
+        assert_eq!(
+            Iso7816.unpad(b"ICE ICE BABY\x81\x00\x00\x00", 16),
+            Err(CryptoError::Iso7816Padding { found: Some(0x81) })
+        );
+        assert_eq!(
+            Iso7816.unpad(&[0; 16], 16),
+            Err(CryptoError::Iso7816Padding { found: None })
+        );
+        assert_eq!(
+            Zero.unpad(&[0; 16], 16),
+            Err(CryptoError::ZeroPadding { zeros: 16 })
+        );
+    }
+}
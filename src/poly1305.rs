@@ -0,0 +1,76 @@
+//! # Poly1305 one-time authenticator (RFC 8439)
+//! The message is split into 16 byte blocks, each read as a little endian number with an extra
+//! `1` byte appended. The tag is `(sum(c_i * r^(q-i+1)) mod 2^130 - 5) + s mod 2^128`.
+//!
+//! `r` and `s` form a one-time key: two tags under the same key give away `r` (see the attacks).
+
+use num_bigint::BigUint;
+
+/// `2^130 - 5`
+pub fn p() -> BigUint {
+    (BigUint::from(1u8) << 130) - BigUint::from(5u8)
+}
+
+/// Clear the bits of `r` the specification requires to be zero
+pub fn clamp(r: &[u8]) -> BigUint {
+    let mut r = r.to_vec();
+    for i in [3, 7, 11, 15].iter() {
+        r[*i] &= 0x0f;
+    }
+    for i in [4, 8, 12].iter() {
+        r[*i] &= 0xfc;
+    }
+    BigUint::from_bytes_le(&r)
+}
+
+/// The message blocks as numbers, including the appended `1` byte
+pub fn blocks(msg: &[u8]) -> Vec<BigUint> {
+    msg.chunks(16)
+        .map(|chunk| {
+            let mut block = chunk.to_vec();
+            block.push(1);
+            BigUint::from_bytes_le(&block)
+        })
+        .collect()
+}
+
+/// Convert the accumulator back into the 16 byte tag, dropping everything above 2^128
+pub fn to_tag(acc: &BigUint) -> Vec<u8> {
+    let mut tag = acc.to_bytes_le();
+    tag.resize(16, 0);
+    tag
+}
+
+/// Compute the 16 byte tag of `msg` with a 32 byte one-time key `r || s`
+pub fn poly1305(key: &[u8], msg: &[u8]) -> Vec<u8> {
+    assert_eq!(key.len(), 32, "invalid key length");
+    let p = p();
+    let r = clamp(&key[..16]);
+    let s = BigUint::from_bytes_le(&key[16..]);
+    let acc = blocks(msg)
+        .iter()
+        .fold(BigUint::from(0u8), |acc, block| (acc + block) * &r % &p);
+    to_tag(&(acc + s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 8439 section 2.5.2
+    #[test]
+    fn rfc_8439_vector() {
+        let key = hex::decode("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b")
+            .unwrap();
+        assert_eq!(
+            hex::encode(poly1305(&key, b"Cryptographic Forum Research Group")),
+            "a8061dc1305136c6c22b8baf0c0127a9"
+        );
+    }
+
+    #[test]
+    fn empty_message_is_s() {
+        let key: Vec<u8> = (0..32).collect();
+        assert_eq!(poly1305(&key, &[]), key[16..].to_vec());
+    }
+}
@@ -1,10 +1,13 @@
 //! # Implement SHA1, trying to proxy the std one first
 //! learned: <<1 != rotate_left -> wrapping vs not
 
+use crate::digest::{update_blocks, Digest, State};
+use crate::hmac::hmac;
+
 const STARTING_STATE: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
 
 pub struct MySha1 {
-    h: [u32; 5],
+    state: State<[u32; 5]>,
 }
 
 impl MySha1 {
@@ -13,8 +16,8 @@ impl MySha1 {
     pub fn padding(len: usize) -> Vec<u8> {
         let mut padding = vec![0x80];
         let mut len_bits = len * 8;
-        // might be off by one
-        if len_bits % 512 > 448 {
+        // no room left for the length, not even when exactly 448 bits are used
+        if len_bits % 512 >= 448 {
             len_bits += 72;
             padding.append(&mut vec![0; 9]);
         }
@@ -57,13 +60,13 @@ impl MySha1 {
             0,
             "input length has to be a multiple of 512 bits"
         );
-        let mut sha1 = MySha1 { h: state };
+        let mut h = state;
         for i in (0..input.len()).step_by(64) {
             let mut block: [u8; 64] = [0; 64];
             block.copy_from_slice(&input[i..i + 64]);
-            do_block(&mut sha1, block);
+            do_block(&mut h, &block);
         }
-        sha1.h
+        h
     }
 
     /// Perform Sha1(key||data)
@@ -73,12 +76,9 @@ impl MySha1 {
         MySha1::hash(MySha1::pad(input))
     }
 
-    /// Sha1 hmac
+    /// HMAC-SHA1 as words, see `hmac::hmac` for any other hash
     pub fn hmac(key: &Vec<u8>, data: &Vec<u8>) -> [u32; 5] {
-        let mut input = key.clone();
-        input.extend_from_slice(data);
-        input.extend_from_slice(key);
-        MySha1::hash(MySha1::pad(input))
+        MySha1::words_from_output(&hmac::<MySha1>(key, data)).unwrap()
     }
 
     /// validate that Sha1(key||data) == mac
@@ -89,7 +89,58 @@ impl MySha1 {
     }
 }
 
-fn do_block(sha1: &mut MySha1, block: [u8; 64]) {
+impl Digest for MySha1 {
+    const BLOCK_SIZE: usize = 64;
+    const OUTPUT_SIZE: usize = 20;
+    type Words = [u32; 5];
+    type Output = [u8; 20];
+
+    fn new() -> Self {
+        MySha1::import_state(State {
+            h: STARTING_STATE,
+            len: 0,
+            buffer: vec![],
+        })
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        update_blocks(&mut self.state, data, 64, do_block);
+    }
+
+    fn finalize(mut self) -> Self::Output {
+        let padding = MySha1::padding(self.state.len as usize);
+        self.update(&padding);
+        let mut output = [0; 20];
+        for (chunk, word) in output.chunks_mut(4).zip(self.state.h.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        output
+    }
+
+    fn export_state(&self) -> State<Self::Words> {
+        self.state.clone()
+    }
+
+    fn import_state(state: State<Self::Words>) -> Self {
+        MySha1 { state }
+    }
+
+    fn padding(len: usize) -> Vec<u8> {
+        MySha1::padding(len)
+    }
+
+    fn words_from_output(output: &Self::Output) -> Option<Self::Words> {
+        let mut words = [0; 5];
+        for (word, chunk) in words.iter_mut().zip(output.chunks(4)) {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(chunk);
+            *word = u32::from_be_bytes(bytes);
+        }
+        Some(words)
+    }
+}
+
+fn do_block(h: &mut [u32; 5], block: &[u8]) {
     let mut w = [0u32; 80];
     // fancy self written byte to u32 converter
     for i in 0..64 {
@@ -103,11 +154,11 @@ fn do_block(sha1: &mut MySha1, block: [u8; 64]) {
     }
     // println!("{:x?}", w[0..16].to_vec());
 
-    let mut a = sha1.h[0];
-    let mut b = sha1.h[1];
-    let mut c = sha1.h[2];
-    let mut d = sha1.h[3];
-    let mut e = sha1.h[4];
+    let mut a = h[0];
+    let mut b = h[1];
+    let mut c = h[2];
+    let mut d = h[3];
+    let mut e = h[4];
 
     for i in 0..80 {
         let mut f = 0;
@@ -140,17 +191,18 @@ fn do_block(sha1: &mut MySha1, block: [u8; 64]) {
         // println!("{}: {:08x?} {:08x?} {:08x?} {:08x?} {:08x?}", i, a, b, c, d, e);
     }
 
-    sha1.h[0] = sha1.h[0].wrapping_add(a);
-    sha1.h[1] = sha1.h[1].wrapping_add(b);
-    sha1.h[2] = sha1.h[2].wrapping_add(c);
-    sha1.h[3] = sha1.h[3].wrapping_add(d);
-    sha1.h[4] = sha1.h[4].wrapping_add(e);
+    h[0] = h[0].wrapping_add(a);
+    h[1] = h[1].wrapping_add(b);
+    h[2] = h[2].wrapping_add(c);
+    h[3] = h[3].wrapping_add(d);
+    h[4] = h[4].wrapping_add(e);
     // println!("{:x?}", sha1.h);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::u32_be_bytes;
 
     #[test]
     fn sha1_working_correctly() {
@@ -179,6 +231,9 @@ mod tests {
         assert_eq!(pad[0], 0x80, "first padding byte");
         assert_eq!(*pad.last().unwrap(), (57 * 8) as u8, "last padding byte");
         assert_eq!(pad.len(), 64 * 2 - 57, "length");
+
+        // exactly 448 bits, the length does not fit either
+        assert_eq!(MySha1::padding(56).len(), 64 * 2 - 56, "length");
     }
 
     #[test]
@@ -193,9 +248,40 @@ mod tests {
 
     #[test]
     fn hmac() {
+        // test case 2 of RFC 2202
+        let mac = MySha1::hmac(&b"Jefe".to_vec(), &b"what do ya want for nothing?".to_vec());
         assert_eq!(
-            MySha1::hmac(&b"a".to_vec(), &b"b".to_vec()),
-            MySha1::hash(MySha1::pad(b"aba".to_vec()))
+            hex::encode(u32_be_bytes(&mac)),
+            "effcdf6ae5eb2fa2d27416d5f184df9c259a7c79"
         )
     }
+
+    #[test]
+    fn streaming_digest() {
+        assert_eq!(
+            hex::encode(MySha1::digest(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        // 56 bytes, the length has to go into an extra block
+        assert_eq!(
+            hex::encode(MySha1::digest(&[b'a'; 56])),
+            "c2db330f6083854c99d4b5bfb6e8f29f201be699"
+        );
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let mut streaming = MySha1::new();
+        for chunk in data.chunks(7) {
+            streaming.update(chunk);
+        }
+        assert_eq!(streaming.finalize(), MySha1::digest(&data));
+
+        // stop in the middle of a block and continue somewhere else
+        let mut first = MySha1::new();
+        first.update(&data[..100]);
+        let state = first.export_state();
+        assert_eq!(state.len, 100);
+        assert_eq!(state.buffer.len(), 100 % 64);
+        let mut second = MySha1::import_state(state);
+        second.update(&data[100..]);
+        assert_eq!(second.finalize(), MySha1::digest(&data));
+    }
 }
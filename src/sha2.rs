@@ -0,0 +1,452 @@
+//! # SHA-2 (FIPS 180-4)
+//! SHA-256 and SHA-512 and their truncated variants SHA-224 and SHA-384, with the same API as `MySha1`.
+//! They are Merkle–Damgård hashes just like SHA-1, so `hash_with_initial_state` and `pad_fake_size`
+//! extend a SHA-256 or SHA-512 MAC just as well (s4c29).
+//! SHA-224 and SHA-384 only output part of the final state, that is what stops the length extension there.
+
+use crate::digest::{update_blocks, Digest, State};
+
+const K256: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const K512: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+const SHA224_STATE: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4,
+];
+const SHA256_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+const SHA384_STATE: [u64; 8] = [
+    0xcbbb9d5dc1059ed8,
+    0x629a292a367cd507,
+    0x9159015a3070dd17,
+    0x152fecd8f70e5939,
+    0x67332667ffc00b31,
+    0x8eb44a8768581511,
+    0xdb0c2e0d64f98fa7,
+    0x47b5481dbefa4fa4,
+];
+const SHA512_STATE: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+/// `0x80`, zeros and the message length in bits, `len_bytes` wide.
+/// `size` is the length that ends up in the padding, usually the same as `len`.
+fn padding(len: usize, size: usize, block_size: usize, len_bytes: usize) -> Vec<u8> {
+    let mut padding = vec![0x80];
+    while !(len + padding.len() + len_bytes).is_multiple_of(block_size) {
+        padding.push(0);
+    }
+    let bits = (size as u128 * 8).to_be_bytes();
+    padding.extend_from_slice(&bits[16 - len_bytes..]);
+    padding
+}
+
+fn do_block_256(h: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for (i, chunk) in block.chunks(4).enumerate() {
+        w[i] = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..64 {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let tmp1 = hh
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K256[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let tmp2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(tmp1);
+        d = c;
+        c = b;
+        b = a;
+        a = tmp1.wrapping_add(tmp2);
+    }
+
+    for (word, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+fn do_block_512(h: &mut [u64; 8], block: &[u8]) {
+    let mut w = [0u64; 80];
+    for (i, chunk) in block.chunks(8).enumerate() {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        w[i] = u64::from_be_bytes(bytes);
+    }
+    for i in 16..80 {
+        let s0 = w[i - 15].rotate_right(1) ^ w[i - 15].rotate_right(8) ^ (w[i - 15] >> 7);
+        let s1 = w[i - 2].rotate_right(19) ^ w[i - 2].rotate_right(61) ^ (w[i - 2] >> 6);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for i in 0..80 {
+        let s1 = e.rotate_right(14) ^ e.rotate_right(18) ^ e.rotate_right(41);
+        let ch = (e & f) ^ (!e & g);
+        let tmp1 = hh
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K512[i])
+            .wrapping_add(w[i]);
+        let s0 = a.rotate_right(28) ^ a.rotate_right(34) ^ a.rotate_right(39);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let tmp2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(tmp1);
+        d = c;
+        c = b;
+        b = a;
+        a = tmp1.wrapping_add(tmp2);
+    }
+
+    for (word, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh].iter()) {
+        *word = word.wrapping_add(*value);
+    }
+}
+
+macro_rules! sha2_variant {
+    ($name:ident, $word:ty, $starting:expr, $do_block:ident, $block_size:expr, $len_bytes:expr, $output_size:expr) => {
+        pub struct $name {
+            state: State<[$word; 8]>,
+        }
+
+        impl $name {
+            /// Obtain only the padding for a certain input length in bytes
+            pub fn padding(len: usize) -> Vec<u8> {
+                padding(len, len, $block_size, $len_bytes)
+            }
+
+            pub fn pad(mut input: Vec<u8>) -> Vec<u8> {
+                input.append(&mut $name::padding(input.len()));
+                input
+            }
+
+            /// Pad `input` as if it was `size` bytes long
+            pub fn pad_fake_size(mut input: Vec<u8>, size: usize) -> Vec<u8> {
+                input.append(&mut padding(input.len(), size, $block_size, $len_bytes));
+                input
+            }
+
+            /// Hash input vector, assumes (mandatory) padding has been applied already
+            pub fn hash(input: Vec<u8>) -> [u8; $output_size] {
+                $name::output(&$name::hash_with_initial_state($starting, input))
+            }
+
+            /// Hash input vector, padding is handled
+            pub fn hash_padded(input: Vec<u8>) -> [u8; $output_size] {
+                $name::hash($name::pad(input))
+            }
+
+            /// Hash input vector with a specific starting state, assumes padding has been applied already.
+            /// Returns the whole state, not just the (possibly truncated) output.
+            pub fn hash_with_initial_state(state: [$word; 8], input: Vec<u8>) -> [$word; 8] {
+                assert_eq!(
+                    input.len() % $block_size,
+                    0,
+                    "input length has to be a multiple of the block size"
+                );
+                let mut h = state;
+                for block in input.chunks($block_size) {
+                    $do_block(&mut h, block);
+                }
+                h
+            }
+
+            /// The first bytes of the big endian state
+            fn output(h: &[$word; 8]) -> [u8; $output_size] {
+                let bytes: Vec<u8> = h
+                    .iter()
+                    .flat_map(|word| word.to_be_bytes().to_vec())
+                    .collect();
+                let mut output = [0; $output_size];
+                output.copy_from_slice(&bytes[..$output_size]);
+                output
+            }
+
+            /// Perform hash(key||data)
+            pub fn keyed_mac(key: &[u8], data: &[u8]) -> [u8; $output_size] {
+                $name::hash_padded([key, data].concat())
+            }
+        }
+
+        impl Digest for $name {
+            const BLOCK_SIZE: usize = $block_size;
+            const OUTPUT_SIZE: usize = $output_size;
+            type Words = [$word; 8];
+            type Output = [u8; $output_size];
+
+            fn new() -> Self {
+                $name::import_state(State {
+                    h: $starting,
+                    len: 0,
+                    buffer: vec![],
+                })
+            }
+
+            fn update(&mut self, data: &[u8]) {
+                update_blocks(&mut self.state, data, $block_size, $do_block);
+            }
+
+            fn finalize(mut self) -> Self::Output {
+                let padding = $name::padding(self.state.len as usize);
+                self.update(&padding);
+                $name::output(&self.state.h)
+            }
+
+            fn export_state(&self) -> State<Self::Words> {
+                self.state.clone()
+            }
+
+            fn import_state(state: State<Self::Words>) -> Self {
+                $name { state }
+            }
+
+            fn padding(len: usize) -> Vec<u8> {
+                $name::padding(len)
+            }
+
+            fn words_from_output(output: &Self::Output) -> Option<Self::Words> {
+                const WORD: usize = std::mem::size_of::<$word>();
+                if $output_size < 8 * WORD {
+                    return None;
+                }
+                let mut words = [0; 8];
+                for (word, chunk) in words.iter_mut().zip(output.chunks(WORD)) {
+                    let mut bytes = [0; WORD];
+                    bytes.copy_from_slice(chunk);
+                    *word = <$word>::from_be_bytes(bytes);
+                }
+                Some(words)
+            }
+        }
+    };
+}
+
+sha2_variant!(MySha224, u32, SHA224_STATE, do_block_256, 64, 8, 28);
+sha2_variant!(MySha256, u32, SHA256_STATE, do_block_256, 64, 8, 32);
+sha2_variant!(MySha384, u64, SHA384_STATE, do_block_512, 128, 16, 48);
+sha2_variant!(MySha512, u64, SHA512_STATE, do_block_512, 128, 16, 64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TWO_BLOCKS_256: &[u8] = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+    const TWO_BLOCKS_512: &[u8] = b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmnhijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu";
+
+    /// The examples of the NIST "Cryptographic Standards and Guidelines"
+    #[test]
+    fn nist_vectors() {
+        assert_eq!(
+            hex::encode(MySha224::hash_padded(b"abc".to_vec())),
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7"
+        );
+        assert_eq!(
+            hex::encode(MySha224::digest(TWO_BLOCKS_256)),
+            "75388b16512776cc5dba5da1fd890150b0c6455cb4f58b1952522525"
+        );
+        assert_eq!(
+            hex::encode(MySha256::hash_padded(b"abc".to_vec())),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex::encode(MySha256::digest(TWO_BLOCKS_256)),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            hex::encode(MySha256::digest(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            hex::encode(MySha384::hash_padded(b"abc".to_vec())),
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7"
+        );
+        assert_eq!(
+            hex::encode(MySha384::digest(TWO_BLOCKS_512)),
+            "09330c33f71147e83d192fc782cd1b4753111b173b3b05d22fa08086e3b0f712fcc7c71a557e2db966c3e9fa91746039"
+        );
+        assert_eq!(
+            hex::encode(MySha512::hash_padded(b"abc".to_vec())),
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f"
+        );
+        assert_eq!(
+            hex::encode(MySha512::digest(TWO_BLOCKS_512)),
+            "8e959b75dae313da8cf4f72814fc143f8f7779c6eb9f7fa17299aeadb6889018501d289e4900f7e4331b99dec4b5433ac7d329eeb6dd26545e96e55b874be909"
+        );
+    }
+
+    #[test]
+    fn one_million_a() {
+        let mut sha256 = MySha256::new();
+        let mut sha512 = MySha512::new();
+        for _ in 0..1000 {
+            sha256.update(&[b'a'; 1000]);
+            sha512.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            hex::encode(sha256.finalize()),
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0"
+        );
+        assert_eq!(
+            hex::encode(sha512.finalize()),
+            "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973ebde0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b"
+        );
+    }
+
+    #[test]
+    fn correct_padding() {
+        assert_eq!(MySha256::padding(55).len(), 9);
+        assert_eq!(MySha256::padding(56).len(), 64 + 8);
+        // SHA-512 has a 128 bit length field
+        let pad = MySha512::padding(111);
+        assert_eq!(pad.len(), 17);
+        assert_eq!(pad[1..15], [0; 14]);
+        assert_eq!(pad[15..], [(111 * 8 >> 8) as u8, (111 * 8) as u8]);
+        assert_eq!(MySha512::padding(112).len(), 128 + 16);
+    }
+
+    /// SHA-256 is just as length-extendable as SHA-1 (s4c29)
+    #[test]
+    fn length_extension() {
+        let key = b"unknown key";
+        let message = b"comment1=cooking%20MCs;userdata=foo".to_vec();
+        let mac = MySha256::keyed_mac(key, &message);
+
+        let glue = MySha256::padding(key.len() + message.len());
+        let suffix = b";admin=true".to_vec();
+        let forged_len = key.len() + message.len() + glue.len() + suffix.len();
+        let state = MySha256::words_from_output(&mac).unwrap();
+        let forged_mac = MySha256::hash_with_initial_state(
+            state,
+            MySha256::pad_fake_size(suffix.clone(), forged_len),
+        );
+
+        let forged_message = [message, glue, suffix].concat();
+        assert_eq!(
+            MySha256::output(&forged_mac),
+            MySha256::keyed_mac(key, &forged_message)
+        );
+        // the truncated variants don't leak their whole state
+        assert_eq!(MySha224::words_from_output(&MySha224::digest(b"abc")), None);
+        assert_eq!(MySha384::words_from_output(&MySha384::digest(b"abc")), None);
+    }
+}
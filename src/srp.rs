@@ -1,7 +1,7 @@
 use crate::dh::{begin_dh_nist, gen_random};
+use crate::digest::Digest;
 use crate::sha1::MySha1;
 use crate::srp::Messages::{Register, SRP1, SRP2, SRP3, SRP4};
-use crate::u32_be_bytes;
 use num_bigint::BigUint;
 use std::ops::{Add, Rem};
 use std::sync::mpsc::{Receiver, Sender};
@@ -16,8 +16,7 @@ pub enum Messages<'a> {
 }
 
 fn vec_to_uint(inp: Vec<u8>) -> BigUint {
-    let hash = MySha1::hash(MySha1::pad(inp));
-    BigUint::from_bytes_be(&u32_be_bytes(&hash))
+    BigUint::from_bytes_be(&MySha1::digest(&inp))
 }
 
 fn uint_to_vec(inp: &BigUint) -> Vec<u8> {
@@ -34,7 +33,7 @@ pub fn n() -> BigUint {
 }
 
 fn hash_then_uint(inp: Vec<u8>) -> BigUint {
-    vec_to_uint(u32_be_bytes(&MySha1::hash_padded(inp)))
+    vec_to_uint(MySha1::digest(&inp).to_vec())
 }
 
 pub fn server(tx: Sender<Messages>, rx: Receiver<Messages>) {
@@ -70,13 +69,13 @@ pub fn server(tx: Sender<Messages>, rx: Receiver<Messages>) {
     let u = hash_then_uint(uints_to_vec(&a_pub, &b_pub));
     let shared_s: BigUint = (a_pub * v.modpow(&u, &n)).modpow(&b, &n);
     println!("s: S={:?}", shared_s);
-    let shared_k = MySha1::hash_padded(uint_to_vec(&shared_s));
+    let shared_k = MySha1::digest(&uint_to_vec(&shared_s));
 
     let hmac = match rx.recv().unwrap() {
         SRP3 { hmac } => hmac,
         _ => panic!("never"),
     };
-    let s_hmac = MySha1::hmac(&u32_be_bytes(&shared_k), &salt.to_vec());
+    let s_hmac = MySha1::hmac(&shared_k.to_vec(), &salt.to_vec());
     println!("c: {:?} == {:?} : s", hmac, s_hmac);
     let auth = hmac == hmac;
     tx.send(SRP4 { auth }).unwrap();
@@ -86,7 +85,7 @@ pub fn client_ext(
     tx: Sender<Messages>,
     rx: Receiver<Messages>,
     a_source: &dyn Fn(&BigUint, &BigUint, &BigUint) -> BigUint,
-    hmac_source: &dyn Fn(&[u8], &[u32]) -> [u32; 5],
+    hmac_source: &dyn Fn(&[u8], &[u8]) -> [u32; 5],
 ) {
     let id = b"client";
     let pass = b"pass";
@@ -117,7 +116,7 @@ pub fn client_ext(
     let shared_s: BigUint =
         (&b_pub + &n - (&k * g.modpow(&x, &n)).rem(&n)).modpow(&(a + u * x), &n);
     println!("c: S={:?}", shared_s);
-    let shared_k = MySha1::hash_padded(uint_to_vec(&shared_s));
+    let shared_k = MySha1::digest(&uint_to_vec(&shared_s));
 
     let hmac1 = hmac_source(&salt, &shared_k);
     tx.send(SRP3 { hmac: hmac1 }).unwrap();
@@ -138,6 +137,6 @@ pub fn client(tx: Sender<Messages>, rx: Receiver<Messages>) {
     client_ext(tx, rx, &|a, g, n| g.modpow(a, n), &get_hmac)
 }
 
-pub fn get_hmac(salt: &[u8], shared_k: &[u32]) -> [u32; 5] {
-    MySha1::hmac(&u32_be_bytes(&shared_k), &salt.to_vec())
+pub fn get_hmac(salt: &[u8], shared_k: &[u8]) -> [u32; 5] {
+    MySha1::hmac(&shared_k.to_vec(), &salt.to_vec())
 }
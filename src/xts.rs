@@ -0,0 +1,263 @@
+//! # XTS-AES (IEEE 1619)
+//! The mode for disk encryption: every sector is encrypted on its own, addressed by its number.
+//! - the key is split into a data key and a tweak key
+//! - `T = E_k2(sector)` and block `j` uses `T_j = T * α^j` in GF(2^128)
+//! - `C_j = E_k1(P_j ^ T_j) ^ T_j`, so every block position of the disk gets its own permutation
+//! - a partial last block steals the end of the previous cipher block, the sector size is not changed
+//!
+//! Unlike CTR (see s4c25) rewriting data does not reuse a keystream.
+//! It is still deterministic: the same plaintext at the same position gives the same ciphertext,
+//! and a change is confined to its 16 byte block.
+
+use crate::blockcipher::{aes, BlockCipher};
+
+pub struct Xts {
+    data: Box<dyn BlockCipher>,
+    tweak: Box<dyn BlockCipher>,
+}
+
+/// Multiply by `α` (`x`), the tweak is little endian in XTS
+fn mul_alpha(tweak: &mut [u8]) {
+    let mut carry = 0;
+    for byte in tweak.iter_mut() {
+        let next_carry = *byte >> 7;
+        *byte = (*byte << 1) | carry;
+        carry = next_carry;
+    }
+    if carry == 1 {
+        tweak[0] ^= 0x87;
+    }
+}
+
+fn xor_into(block: &mut [u8], other: &[u8]) {
+    for (b, o) in block.iter_mut().zip(other.iter()) {
+        *b ^= o;
+    }
+}
+
+impl Xts {
+    /// `key` is the data key followed by the tweak key, 32 or 64 bytes
+    pub fn new(key: &[u8]) -> Self {
+        assert!(
+            key.len() == 32 || key.len() == 64,
+            "needs two AES-128 or AES-256 keys"
+        );
+        let (data, tweak) = key.split_at(key.len() / 2);
+        Xts {
+            data: aes(data),
+            tweak: aes(tweak),
+        }
+    }
+
+    /// Every sector and the last, possibly shorter one need a full block for ciphertext stealing,
+    /// checked up front so that a bad layout fails before any sector is processed
+    fn check_layout(len: usize, sector_size: usize) {
+        assert!(sector_size >= 16, "a sector needs at least one full block");
+        assert!(
+            len.is_multiple_of(sector_size) || len % sector_size >= 16,
+            "the last sector needs at least one full block"
+        );
+    }
+
+    fn tweaks(&self, sector: u128, blocks: usize) -> Vec<Vec<u8>> {
+        let mut tweak = self.tweak.encrypt_block(&sector.to_le_bytes());
+        let mut tweaks = Vec::with_capacity(blocks);
+        for _ in 0..blocks {
+            tweaks.push(tweak.clone());
+            mul_alpha(&mut tweak);
+        }
+        tweaks
+    }
+
+    fn process_block(&self, block: &[u8], tweak: &[u8], encrypt: bool) -> Vec<u8> {
+        let mut block = block.to_vec();
+        xor_into(&mut block, tweak);
+        let mut block = if encrypt {
+            self.data.encrypt_block(&block)
+        } else {
+            self.data.decrypt_block(&block)
+        };
+        xor_into(&mut block, tweak);
+        block
+    }
+
+    fn process(&self, sector: u128, data: &[u8], encrypt: bool) -> Vec<u8> {
+        assert!(data.len() >= 16, "a sector needs at least one full block");
+        let full = data.len() / 16;
+        let partial = data.len() % 16;
+        let tweaks = self.tweaks(sector, full + 1);
+        let mut result = Vec::with_capacity(data.len());
+        // with stealing, the last full block is handled together with the partial one
+        let plain_full = if partial == 0 { full } else { full - 1 };
+        for (block, tweak) in data.chunks(16).take(plain_full).zip(tweaks.iter()) {
+            result.extend(self.process_block(block, tweak, encrypt));
+        }
+        if partial == 0 {
+            return result;
+        }
+
+        // decryption has to use the tweaks of the last two blocks in the opposite order
+        let (first_tweak, second_tweak) = if encrypt {
+            (&tweaks[full - 1], &tweaks[full])
+        } else {
+            (&tweaks[full], &tweaks[full - 1])
+        };
+        let last_full = &data[(full - 1) * 16..full * 16];
+        let tail = &data[full * 16..];
+        let stolen = self.process_block(last_full, first_tweak, encrypt);
+        let mut block = tail.to_vec();
+        block.extend(&stolen[partial..]);
+        result.extend(self.process_block(&block, second_tweak, encrypt));
+        result.extend(&stolen[..partial]);
+        result
+    }
+
+    /// Encrypt one sector, the result has the same length
+    pub fn encrypt_sector(&self, sector: u128, plain: &[u8]) -> Vec<u8> {
+        self.process(sector, plain, true)
+    }
+
+    pub fn decrypt_sector(&self, sector: u128, cipher: &[u8]) -> Vec<u8> {
+        self.process(sector, cipher, false)
+    }
+
+    /// Encrypt a whole disk image, sector by sector starting with sector 0.
+    /// Panics if the last sector is shorter than a block.
+    pub fn encrypt(&self, plain: &[u8], sector_size: usize) -> Vec<u8> {
+        Self::check_layout(plain.len(), sector_size);
+        plain
+            .chunks(sector_size)
+            .enumerate()
+            .flat_map(|(i, sector)| self.encrypt_sector(i as u128, sector))
+            .collect()
+    }
+
+    pub fn decrypt(&self, cipher: &[u8], sector_size: usize) -> Vec<u8> {
+        Self::check_layout(cipher.len(), sector_size);
+        cipher
+            .chunks(sector_size)
+            .enumerate()
+            .flat_map(|(i, sector)| self.decrypt_sector(i as u128, sector))
+            .collect()
+    }
+
+    /// Overwrite plaintext at `offset`, only the touched sectors are decrypted and encrypted again
+    pub fn edit(&self, cipher: &[u8], sector_size: usize, offset: usize, new: &[u8]) -> Vec<u8> {
+        Self::check_layout(cipher.len(), sector_size);
+        assert!(offset + new.len() <= cipher.len());
+        let mut result = cipher.to_vec();
+        if new.is_empty() {
+            return result;
+        }
+        let first = offset / sector_size;
+        let last = (offset + new.len() - 1) / sector_size;
+        for sector in first..=last {
+            let start = sector * sector_size;
+            let end = (start + sector_size).min(cipher.len());
+            let mut plain = self.decrypt_sector(sector as u128, &cipher[start..end]);
+            for (i, byte) in plain.iter_mut().enumerate() {
+                let position = start + i;
+                if position >= offset && position < offset + new.len() {
+                    *byte = new[position - offset];
+                }
+            }
+            result.splice(start..end, self.encrypt_sector(sector as u128, &plain));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn h(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    /// IEEE 1619 vectors 1 and 4
+    #[test]
+    fn full_blocks() {
+        let xts = Xts::new(&[0; 32]);
+        assert_eq!(
+            hex::encode(xts.encrypt_sector(0, &[0; 32])),
+            "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e"
+        );
+
+        let xts = Xts::new(&h(
+            "2718281828459045235360287471352631415926535897932384626433832795",
+        ));
+        let plain: Vec<u8> = (0..512).map(|i| i as u8).collect();
+        let cipher = xts.encrypt_sector(0, &plain);
+        assert_eq!(
+            hex::encode(&cipher[..32]),
+            "27a7479befa1d476489f308cd4cfa6e2a96e4bbe3208ff25287dd3819616e89c"
+        );
+        assert_eq!(
+            hex::encode(&cipher[480..]),
+            "eb4a427d1923ce3ff262735779a418f20a282df920147beabe421ee5319d0568"
+        );
+        assert_eq!(xts.decrypt_sector(0, &cipher), plain);
+    }
+
+    /// Partial last blocks, vectors 15 and 18 of IEEE 1619 (data unit `9a 78 56 34 12`, little endian)
+    #[test]
+    fn ciphertext_stealing() {
+        let xts = Xts::new(&h(
+            "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0",
+        ));
+        let plain: Vec<u8> = (0..20).collect();
+        let cipher = xts.encrypt_sector(0x123456789a, &plain[..17]);
+        assert_eq!(hex::encode(&cipher), "6c1625db4671522d3d7599601de7ca09ed");
+        assert_eq!(
+            xts.decrypt_sector(0x123456789a, &cipher),
+            plain[..17].to_vec()
+        );
+
+        let cipher = xts.encrypt_sector(0x123456789a, &plain);
+        assert_eq!(
+            hex::encode(&cipher),
+            "9d84c813f719aa2c7be3f66171c7c5c2edbf9dac"
+        );
+        assert_eq!(xts.decrypt_sector(0x123456789a, &cipher), plain);
+    }
+
+    #[test]
+    fn edit_only_touches_its_block() {
+        let xts = Xts::new(&[crate::random_128_bit(), crate::random_128_bit()].concat());
+        let plain = b"YELLOW SUBMARINE".repeat(20);
+        let cipher = xts.encrypt(&plain, 64);
+        assert_eq!(xts.decrypt(&cipher, 64), plain);
+        // the same plaintext encrypts differently in every block
+        assert_ne!(cipher[..16], cipher[16..32]);
+
+        let edited = xts.edit(&cipher, 64, 70, b"ORANGE");
+        let mut expected = plain.clone();
+        expected.splice(70..76, b"ORANGE".iter().cloned());
+        assert_eq!(xts.decrypt(&edited, 64), expected);
+        let changed: Vec<usize> = (0..cipher.len() / 16)
+            .filter(|i| cipher[i * 16..(i + 1) * 16] != edited[i * 16..(i + 1) * 16])
+            .collect();
+        assert_eq!(changed, vec![4]);
+        // writing back the original restores the original ciphertext
+        assert_eq!(xts.edit(&edited, 64, 70, &plain[70..76]), cipher);
+    }
+
+    #[test]
+    fn short_trailing_sector() {
+        let xts = Xts::new(&[7; 32]);
+        // a trailing sector of 20 bytes is fine, it steals from its own first block
+        let plain = b"YELLOW SUBMARINE".repeat(9)[..84].to_vec();
+        let cipher = xts.encrypt(&plain, 64);
+        assert_eq!(cipher[64..], xts.encrypt_sector(1, &plain[64..])[..]);
+        assert_eq!(xts.decrypt(&cipher, 64), plain);
+        assert_eq!(xts.edit(&cipher, 64, 80, b"ORAN")[..64], cipher[..64]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the last sector needs at least one full block")]
+    fn trailing_sector_shorter_than_a_block() {
+        let xts = Xts::new(&[7; 32]);
+        xts.encrypt(&[0; 64 + 15], 64);
+    }
+}